@@ -0,0 +1,520 @@
+use crate::config::Config;
+use crate::data::Value;
+use crate::diagnostic::Diagnostic;
+use crate::l5x;
+use crate::parser::SectionKind;
+use crate::rename;
+use crate::udt::{self, Udt, UdtMember};
+use std::io::Cursor;
+
+/// Parameters every Add-On Instruction has, which members cannot be named
+const BUILT_IN_PARAMETERS: [&str; 2] = ["EnableIn", "EnableOut"];
+
+/// The sections of the interface of one block, which become one Add-On Instruction
+struct Block<'a> {
+    name: String,
+    sections: Vec<(SectionKind, &'a Udt)>,
+}
+
+/// Group the UDTs split from block interfaces by their block, in the order of the blocks
+fn group_blocks(sections: &[Udt]) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = vec![];
+
+    for udt in sections {
+        let Some((block, kind)) = &udt.section else {
+            continue;
+        };
+        let name = rename::logix_name(block);
+        match blocks
+            .iter_mut()
+            .find(|other| other.name.eq_ignore_ascii_case(&name))
+        {
+            Some(other) => other.sections.push((*kind, udt)),
+            None => blocks.push(Block {
+                name,
+                sections: vec![(*kind, udt)],
+            }),
+        }
+    }
+    blocks
+}
+
+/// Get the name of the Add-On Instruction of the last block, which names the document
+pub fn target_name(sections: &[Udt]) -> Option<String> {
+    group_blocks(sections).pop().map(|block| block.name)
+}
+
+/// Get the data type of a member, BOOLs packed into a hidden member are BOOL parameters
+fn data_type(member: &UdtMember) -> &str {
+    if member.target.is_some() {
+        "BOOL"
+    } else {
+        &member.data_type
+    }
+}
+
+/// Input and output parameters must be single atomic values, anything else is passed InOut
+fn atomic(member: &UdtMember) -> bool {
+    udt::numeric_type(data_type(member)) && member.element_count().is_none()
+}
+
+/// Get the usage of a parameter, None for members that are local tags
+fn usage(kind: SectionKind, member: &UdtMember) -> Option<&'static str> {
+    match kind {
+        SectionKind::Input if atomic(member) => Some("Input"),
+        SectionKind::Output if atomic(member) => Some("Output"),
+        SectionKind::Input | SectionKind::Output | SectionKind::InOut => Some("InOut"),
+        _ => None,
+    }
+}
+
+/// Warn about inputs and outputs that are passed InOut and members named like the
+/// parameters every Add-On Instruction has
+pub fn check_parameters(sections: &[Udt], diagnostics: &mut Vec<Diagnostic>) {
+    for block in group_blocks(sections) {
+        for (kind, udt) in &block.sections {
+            for member in udt.members.iter().filter(|member| !member.hidden) {
+                if BUILT_IN_PARAMETERS
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&member.name))
+                {
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            "aoi-parameter",
+                            format!(
+                                "{}.{}: every Add-On Instruction has a parameter of this name",
+                                block.name, member.name
+                            ),
+                        )
+                        .with_span(member.span)
+                        .with_file(udt.file.clone())
+                        .with_help("rename the member, Studio 5000 rejects the duplicate"),
+                    );
+                }
+
+                if matches!(kind, SectionKind::Input | SectionKind::Output)
+                    && usage(*kind, member) == Some("InOut")
+                {
+                    let what = if member.element_count().is_some() {
+                        format!("an array of {}", member.data_type)
+                    } else {
+                        member.data_type.clone()
+                    };
+                    diagnostics.push(
+                        Diagnostic::warning(
+                            "aoi-parameter",
+                            format!(
+                                "{}.{}: {} is not atomic, it is passed as an InOut parameter",
+                                block.name, member.name, what
+                            ),
+                        )
+                        .with_span(member.span)
+                        .with_file(udt.file.clone())
+                        .with_help(
+                            "Add-On Instructions only take single BOOL, integer and REAL values by value",
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Create the default data of an atomic parameter or local tag from its start value
+fn write_default_data(
+    member: &UdtMember,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    if !atomic(member) {
+        return Ok(());
+    }
+
+    let data_type = data_type(member);
+    let Value::Number(value) = l5x::start_value(member, data_type, 0) else {
+        return Ok(());
+    };
+    let mut attributes = vec![("DataType", data_type)];
+    if let Some(radix) = l5x::data_radix(data_type) {
+        attributes.push(("Radix", radix));
+    }
+    attributes.push(("Value", &value));
+
+    writer
+        .create_element("DefaultData")
+        .with_attribute(("Format", "Decorated"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("DataValue")
+                .with_attributes(attributes)
+                .write_empty()?;
+            Ok::<_, quick_xml::Error>(())
+        })?;
+    Ok(())
+}
+
+/// Get the radix and dimensions of a parameter or local tag, which only atomic types have a radix for
+fn radix_and_dimensions(member: &UdtMember) -> (Option<&'static str>, Option<String>) {
+    let data_type = data_type(member);
+    let radix = if udt::numeric_type(data_type) {
+        Some(member.radix.map_or_else(
+            || l5x::data_radix(data_type).unwrap_or("Decimal"),
+            |radix| radix.as_str(),
+        ))
+    } else {
+        None
+    };
+    let dimensions = member.element_count().map(|count| count.to_string());
+    (radix, dimensions)
+}
+
+/// Create the parameter elements, starting with the EnableIn and EnableOut every
+/// Add-On Instruction has
+fn write_parameters(
+    block: &Block,
+    config: &Config,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    for (name, usage) in BUILT_IN_PARAMETERS.into_iter().zip(["Input", "Output"]) {
+        writer
+            .create_element("Parameter")
+            .with_attributes([
+                ("Name", name),
+                ("TagType", "Base"),
+                ("DataType", "BOOL"),
+                ("Usage", usage),
+                ("Radix", "Decimal"),
+                ("Required", "false"),
+                ("Visible", "false"),
+                ("ExternalAccess", "Read Only"),
+            ])
+            .write_empty()?;
+    }
+
+    for (kind, udt) in &block.sections {
+        for member in udt.members.iter().filter(|member| !member.hidden) {
+            let Some(usage) = usage(*kind, member) else {
+                continue;
+            };
+            let (radix, dimensions) = radix_and_dimensions(member);
+            let external_access = member
+                .external_access(config.default_external_access)
+                .as_str();
+
+            let mut attributes = vec![
+                ("Name", member.name.as_str()),
+                ("TagType", "Base"),
+                ("DataType", data_type(member)),
+            ];
+            if let Some(dimensions) = &dimensions {
+                attributes.push(("Dimensions", dimensions));
+            }
+            attributes.push(("Usage", usage));
+            attributes.extend(radix.map(|radix| ("Radix", radix)));
+            match usage {
+                "Input" => attributes.extend([
+                    ("Required", "false"),
+                    ("Visible", "true"),
+                    ("ExternalAccess", external_access),
+                ]),
+                "Output" => attributes.extend([
+                    ("Required", "false"),
+                    ("Visible", "true"),
+                    ("ExternalAccess", "Read Only"),
+                ]),
+                _ => attributes.extend([
+                    ("Required", "true"),
+                    ("Visible", "true"),
+                    ("Constant", "false"),
+                ]),
+            }
+
+            writer
+                .create_element("Parameter")
+                .with_attributes(attributes)
+                .write_inner_content(|writer| {
+                    l5x::write_description(member.description.as_deref(), writer)?;
+                    if usage != "InOut" {
+                        write_default_data(member, writer)?;
+                    }
+                    Ok::<_, quick_xml::Error>(())
+                })?;
+        }
+    }
+    Ok(())
+}
+
+/// Create the local tag elements of the static and temporary members
+fn write_local_tags(
+    block: &Block,
+    config: &Config,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    for (kind, udt) in &block.sections {
+        for member in udt.members.iter().filter(|member| !member.hidden) {
+            if usage(*kind, member).is_some() {
+                continue;
+            }
+            let (radix, dimensions) = radix_and_dimensions(member);
+            let external_access = member
+                .external_access(config.default_external_access)
+                .as_str();
+
+            let mut attributes = vec![
+                ("Name", member.name.as_str()),
+                ("DataType", data_type(member)),
+            ];
+            if let Some(dimensions) = &dimensions {
+                attributes.push(("Dimensions", dimensions));
+            }
+            attributes.extend(radix.map(|radix| ("Radix", radix)));
+            attributes.push(("ExternalAccess", external_access));
+
+            writer
+                .create_element("LocalTag")
+                .with_attributes(attributes)
+                .write_inner_content(|writer| {
+                    l5x::write_description(member.description.as_deref(), writer)?;
+                    write_default_data(member, writer)
+                })?;
+        }
+    }
+    Ok(())
+}
+
+/// Create the dependency elements for the UDTs and custom length strings the parameters
+/// and local tags use, directly or through other UDTs
+fn write_dependencies(
+    block: &Block,
+    udts: &[Udt],
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    let mut names: Vec<&str> = vec![];
+    for (_, udt) in &block.sections {
+        for dependency in udt::all_dependencies(udt, udts) {
+            if !names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&dependency.name))
+            {
+                names.push(&dependency.name);
+            }
+        }
+    }
+
+    if names.is_empty() {
+        return Ok(());
+    }
+    writer
+        .create_element("Dependencies")
+        .write_inner_content(|writer| {
+            for name in names {
+                writer
+                    .create_element("Dependency")
+                    .with_attributes([("Type", "DataType"), ("Name", name)])
+                    .write_empty()?;
+            }
+            Ok::<_, quick_xml::Error>(())
+        })?;
+    Ok(())
+}
+
+/// Create the element of an Add-On Instruction, with an empty ladder routine for the logic
+fn write_definition(
+    block: &Block,
+    usage: &str,
+    udts: &[Udt],
+    config: &Config,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    let software_revision = format!("v{}", config.software_revision);
+    let description = block
+        .sections
+        .first()
+        .and_then(|(_, udt)| udt.description.as_deref());
+
+    writer
+        .create_element("AddOnInstructionDefinition")
+        .with_attributes([
+            ("Use", usage),
+            ("Name", block.name.as_str()),
+            ("Revision", "1.0"),
+            ("ExecutePrescan", "false"),
+            ("ExecutePostscan", "false"),
+            ("ExecuteEnableInFalse", "false"),
+            ("SoftwareRevision", software_revision.as_str()),
+        ])
+        .write_inner_content(|writer| {
+            l5x::write_description(description, writer)?;
+            writer
+                .create_element("Parameters")
+                .write_inner_content(|writer| write_parameters(block, config, writer))?;
+            writer
+                .create_element("LocalTags")
+                .write_inner_content(|writer| write_local_tags(block, config, writer))?;
+            writer
+                .create_element("Routines")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("Routine")
+                        .with_attributes([("Name", "Logic"), ("Type", "RLL")])
+                        .write_inner_content(|writer| {
+                            writer.create_element("RLLContent").write_empty()?;
+                            Ok::<_, quick_xml::Error>(())
+                        })?;
+                    Ok::<_, quick_xml::Error>(())
+                })?;
+            write_dependencies(block, udts, writer)
+        })?;
+    Ok(())
+}
+
+/// Generates an L5X file with an Add-On Instruction per block the sections were split from,
+/// and the other UDTs as the data types they use. The last block is the target
+pub fn create_l5x(
+    udts: &[Udt],
+    sections: &[Udt],
+    config: &Config,
+) -> Result<quick_xml::Writer<Cursor<Vec<u8>>>, quick_xml::Error> {
+    let mut writer = l5x::new_writer(config);
+    let blocks = group_blocks(sections);
+    let target = blocks.last().expect("An L5X has at least one target");
+
+    let export_date = l5x::export_date(config);
+    let mut attributes = vec![
+        ("SchemaRevision", config.schema_revision.as_str()),
+        ("SoftwareRevision", config.software_revision.as_str()),
+        ("TargetName", target.name.as_str()),
+        ("TargetType", "AddOnInstructionDefinition"),
+        ("TargetRevision", "1.0"),
+        ("ContainsContext", "true"),
+        ("ExportDate", export_date.as_str()),
+        ("ExportOptions", "References NoRawData DecoratedData Context Dependencies ForceProtectedEncoding AllProjDocTrans"),
+    ];
+    if config.no_export_date {
+        attributes.retain(|(name, _)| *name != "ExportDate");
+    }
+
+    writer
+        .create_element("RSLogix5000Content")
+        .with_attributes(attributes)
+        .write_inner_content(|writer| {
+            writer
+                .create_element("Controller")
+                .with_attributes([
+                    ("Use", "Context"),
+                    ("Name", config.controller_name.as_str()),
+                ])
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("DataTypes")
+                        .with_attribute(("Use", "Context"))
+                        .write_inner_content(|writer| {
+                            for udt in udts {
+                                let dependencies = udt::direct_dependencies(udt, udts);
+                                l5x::write_data_type(
+                                    udt,
+                                    &dependencies,
+                                    "Context",
+                                    config,
+                                    writer,
+                                )?;
+                            }
+                            Ok::<_, quick_xml::Error>(())
+                        })?;
+                    writer
+                        .create_element("AddOnInstructionDefinitions")
+                        .with_attribute(("Use", "Context"))
+                        .write_inner_content(|writer| {
+                            for (index, block) in blocks.iter().enumerate() {
+                                let usage = if index + 1 == blocks.len() {
+                                    "Target"
+                                } else {
+                                    "Context"
+                                };
+                                write_definition(block, usage, udts, config, writer)?;
+                            }
+                            Ok::<_, quick_xml::Error>(())
+                        })?;
+                    Ok::<_, quick_xml::Error>(())
+                })?;
+            Ok::<_, quick_xml::Error>(())
+        })?;
+
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InterfaceLayout;
+    use crate::verify;
+
+    const BLOCK: &str = r#"TYPE "Motor_T"
+   STRUCT
+      speed : Real;
+   END_STRUCT;
+END_TYPE
+
+FUNCTION_BLOCK "Motor"
+   VAR_INPUT
+      start : Bool := TRUE;
+      cfg : "Motor_T";
+   END_VAR
+   VAR_OUTPUT
+      running : Bool;
+   END_VAR
+   VAR
+      count : DInt := 3;
+   END_VAR
+BEGIN
+END_FUNCTION_BLOCK
+"#;
+
+    /// Convert a block source to an Add-On Instruction, returning the L5X and the diagnostics
+    fn convert(source: &str) -> (String, Vec<Diagnostic>) {
+        let config = Config {
+            interface: InterfaceLayout::Aoi,
+            no_export_date: true,
+            ..Config::default()
+        };
+        let mut diagnostics = vec![];
+        let udts = udt::get_udts(source, &config, &mut diagnostics).unwrap();
+        let (sections, udts): (Vec<Udt>, Vec<Udt>) =
+            udts.into_iter().partition(|udt| udt.section.is_some());
+        check_parameters(&sections, &mut diagnostics);
+
+        let l5x = create_l5x(&udts, &sections, &config).unwrap().into_inner();
+        verify::verify(l5x.get_ref(), &[]).unwrap();
+        (String::from_utf8(l5x.into_inner()).unwrap(), diagnostics)
+    }
+
+    #[test]
+    fn mirrors_the_interface_of_a_block() {
+        let (l5x, _) = convert(BLOCK);
+        let position = |text: &str| {
+            l5x.find(text)
+                .unwrap_or_else(|| panic!("{} is missing", text))
+        };
+
+        assert!(l5x.contains(r#"TargetName="Motor" TargetType="AddOnInstructionDefinition""#));
+        assert!(
+            position(r#"Parameter Name="EnableIn""#) < position(r#"Parameter Name="EnableOut""#)
+        );
+        assert!(position(r#"Parameter Name="EnableOut""#) < position(r#"Parameter Name="start""#));
+        assert!(l5x.contains(r#"Name="start" TagType="Base" DataType="BOOL" Usage="Input""#));
+        assert!(l5x.contains(r#"Name="cfg" TagType="Base" DataType="Motor_T" Usage="InOut""#));
+        assert!(l5x.contains(r#"Name="running" TagType="Base" DataType="BOOL" Usage="Output""#));
+        assert!(l5x.contains(r#"<LocalTag Name="count" DataType="DINT""#));
+        assert!(l5x.contains(r#"<DataType Use="Context" Name="Motor_T""#));
+    }
+
+    #[test]
+    fn reports_members_named_like_built_in_parameters() {
+        let (_, diagnostics) = convert(
+            "FUNCTION_BLOCK \"Valve\"\n   VAR_INPUT\n      EnableIn : Bool;\n   END_VAR\nBEGIN\nEND_FUNCTION_BLOCK",
+        );
+
+        assert!(diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.code == "aoi-parameter"));
+    }
+}
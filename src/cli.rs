@@ -0,0 +1,483 @@
+use crate::config::{
+    schema_revision, software_revision, BitStrings, BoolArrays, BoolPacking, Chars, DefaultString,
+    Dtl, ExternalAccess, IndentChar, InterfaceLayout, Layout, LowerBounds, Preset, SystemTypes,
+    UnknownTypes, WideStrings,
+};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+
+/// Exit codes, kept in sync with `Error::exit_code`, and the precedence of settings
+const EXIT_CODES: &str = "Exit codes:
+  0   Success
+  1   One or more files of a batch failed
+  2   The input could not be parsed
+  3   The input uses an unsupported construct
+  4   A file could not be read or written
+  5   The L5X could not be generated
+  6   --check or validate found warnings, or --require-version a UDT without VERSION
+  7   The config file is invalid
+  64  Invalid command line arguments
+
+Every option can also be set with the UDTCONV_* environment variable shown next to it.
+Options on the command line take precedence over environment variables,
+which take precedence over the config file";
+
+/// This is a tool for converting UDT files exported from TIA Portal
+/// to an L5X XML format to import into Studio 5000
+#[derive(Parser)]
+#[command(
+    version,
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true,
+    after_help = EXIT_CODES
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Arguments used when no subcommand is given, equivalent to `convert`
+    #[command(flatten)]
+    pub convert: Option<ConvertArgs>,
+
+    /// Report more details, use twice to report every converted member
+    #[arg(short, long, action = ArgAction::Count, global = true, env = "UDTCONV_VERBOSE")]
+    pub verbose: u8,
+
+    /// Only report errors
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        env = "UDTCONV_QUIET"
+    )]
+    pub quiet: bool,
+
+    /// Load settings from a TOML config file, defaults to udtconv.toml if it exists
+    #[arg(short, long, global = true, env = "UDTCONV_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// How to report warnings and errors. `json` prints one JSON object per line on stderr
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        global = true,
+        env = "UDTCONV_MESSAGE_FORMAT"
+    )]
+    pub message_format: MessageFormat,
+}
+
+/// Format of warnings and errors
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum MessageFormat {
+    /// Human readable messages with the offending source lines
+    #[default]
+    Human,
+    /// One JSON object per message with code, severity, file, span, message and help
+    Json,
+}
+
+// Parsed once at startup, so the size of the convert arguments doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Command {
+    /// Convert a UDT file to an L5X file (default)
+    Convert(ConvertArgs),
+    /// List the UDTs found in a UDT file with their version, description,
+    /// member count and referenced UDTs
+    List(InputArgs),
+    /// Parse a UDT file and generate the L5X without writing it, failing on any warnings
+    Validate(InputArgs),
+    /// Run every conversion listed in a TOML manifest and report the result of each
+    Batch(ManifestArgs),
+}
+
+#[derive(Args, Clone, Default)]
+pub struct ConvertArgs {
+    /// Specify a UDT file to use as input, or `-` for stdin.
+    /// If a directory is given, every .udt, .db, .scl and .xml file inside it is converted.
+    /// Glob patterns such as "exports/**/*.udt" are also accepted
+    #[arg(short, long, env = "UDTCONV_INPUT")]
+    pub input: Option<PathBuf>,
+
+    /// Specify the location and name to save the L5X, or `-` for stdout.
+    /// Must be a directory if the input is a directory. For glob patterns,
+    /// a directory (existing or ending in a separator) gets one L5X per file,
+    /// otherwise all matched files are combined into one L5X.
+    /// If omitted, each L5X is named after its target UDT, see `--output-dir`
+    #[arg(short, long, env = "UDTCONV_OUTPUT")]
+    pub output: Option<PathBuf>,
+
+    /// Directory for output files when `--output` is not given, named after their target UDT.
+    /// Defaults to the directory of the input
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with = "output",
+        env = "UDTCONV_OUTPUT_DIR"
+    )]
+    pub output_dir: Option<PathBuf>,
+
+    /// Write one L5X per UDT into this directory instead of a single combined L5X,
+    /// each named after its UDT
+    #[arg(
+        short,
+        long,
+        value_name = "DIR",
+        conflicts_with = "output",
+        env = "UDTCONV_SPLIT_OUTPUT"
+    )]
+    pub split_output: Option<PathBuf>,
+
+    /// Read the UDTs from the clipboard instead of a file
+    #[arg(long, conflicts_with_all = ["input", "watch"], env = "UDTCONV_FROM_CLIPBOARD")]
+    pub from_clipboard: bool,
+
+    /// Copy the L5X to the clipboard instead of writing a file
+    #[arg(long, conflicts_with_all = ["output", "output_dir", "split_output"], env = "UDTCONV_TO_CLIPBOARD")]
+    pub to_clipboard: bool,
+
+    /// Overwrite output files that already exist
+    #[arg(short, long, env = "UDTCONV_FORCE")]
+    pub force: bool,
+
+    /// Back up output files that already exist with a timestamp before overwriting them
+    #[arg(short, long, env = "UDTCONV_BACKUP")]
+    pub backup: bool,
+
+    /// Keep running and convert again whenever the input changes
+    #[arg(short, long, conflicts_with = "check", env = "UDTCONV_WATCH")]
+    pub watch: bool,
+
+    /// Only convert UDTs whose name matches this wildcard pattern, can be repeated
+    /// or comma separated
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        env = "UDTCONV_ONLY",
+        value_delimiter = ','
+    )]
+    pub only: Vec<String>,
+
+    /// Don't convert UDTs whose name matches this wildcard pattern, can be repeated
+    /// or comma separated
+    #[arg(
+        long,
+        value_name = "PATTERN",
+        env = "UDTCONV_EXCLUDE",
+        value_delimiter = ','
+    )]
+    pub exclude: Vec<String>,
+
+    /// UDT file, directory or glob pattern with UDTs the input may reference, can be repeated
+    /// or comma separated. Referenced UDTs are added to the L5X along with the UDTs they reference
+    #[arg(
+        short = 'I',
+        long,
+        visible_short_alias = 'L',
+        visible_alias = "include",
+        value_name = "PATH",
+        env = "UDTCONV_LIBRARY",
+        value_delimiter = ','
+    )]
+    pub library: Vec<PathBuf>,
+
+    /// Only warn about referenced UDTs found in the library instead of adding them
+    #[arg(long, env = "UDTCONV_NO_AUTO_INCLUDE")]
+    pub no_auto_include: bool,
+
+    /// Rename UDTs and members using a CSV (`siemens,logix` per line) or TOML file.
+    /// Keys are UDT names or `Udt.Member`, references to renamed UDTs are updated
+    #[arg(long, value_name = "FILE", env = "UDTCONV_RENAME_MAP")]
+    pub rename_map: Option<PathBuf>,
+
+    /// Convert a Siemens type to the given Rockwell type, such as `WORD=INT`, taking
+    /// precedence over the `types` of the config. Can be repeated or comma separated
+    #[arg(
+        long,
+        value_name = "SIEMENS=ROCKWELL",
+        env = "UDTCONV_MAP",
+        value_delimiter = ',',
+        value_parser = parse_mapping
+    )]
+    pub map: Vec<(String, String)>,
+
+    /// Add a prefix to the name of every converted UDT, such as `S7_`
+    #[arg(long, value_name = "PREFIX", env = "UDTCONV_TYPE_PREFIX")]
+    pub type_prefix: Option<String>,
+
+    /// Add a suffix to the name of every converted UDT
+    #[arg(long, value_name = "SUFFIX", env = "UDTCONV_TYPE_SUFFIX")]
+    pub type_suffix: Option<String>,
+
+    /// Add a controller tag for every global DB, named after the DB
+    /// and using the UDT generated from its structure. The tag data is decorated and holds
+    /// the start values after `BEGIN`, or the initial values of the members
+    #[arg(long, env = "UDTCONV_DB_TAGS")]
+    pub db_tags: bool,
+
+    /// Add a controller tag of the target UDT, so there is an instance to map after import
+    #[arg(long, env = "UDTCONV_EMIT_TAGS")]
+    pub emit_tags: bool,
+
+    /// Name of the tag added by `--emit-tags`, defaults to the name of the target UDT
+    #[arg(
+        long,
+        value_name = "NAME",
+        requires = "emit_tags",
+        env = "UDTCONV_TAG_NAME"
+    )]
+    pub tag_name: Option<String>,
+
+    /// Add the tags to this program instead of the controller, for importing into
+    /// program-scoped organizations. Data types are always controller-scoped in Logix
+    #[arg(long, value_name = "NAME", env = "UDTCONV_PROGRAM")]
+    pub program: Option<String>,
+
+    /// How the interface of FBs and FCs is converted, defaults to one UDT per section.
+    /// `aoi` writes an Add-On Instruction per FB with the other UDTs as its data types.
+    /// `skip` only converts the data types of SCL sources mixing types and code
+    #[arg(long, value_enum, env = "UDTCONV_INTERFACE")]
+    pub interface: Option<InterfaceLayout>,
+
+    /// How WSTRING and WCHAR members are converted, defaults to narrowing them to STRING and SINT
+    #[arg(long, value_enum, env = "UDTCONV_WIDE_STRINGS")]
+    pub wide_strings: Option<WideStrings>,
+
+    /// How STRING members without a length are converted, defaults to the 82 character
+    /// STRING of Logix although they hold 254 characters in TIA Portal
+    #[arg(long, value_enum, env = "UDTCONV_DEFAULT_STRING")]
+    pub default_string: Option<DefaultString>,
+
+    /// Length of the STRING_N used by `--default-string sized`, defaults to 254
+    #[arg(long, value_name = "LENGTH", env = "UDTCONV_DEFAULT_STRING_LENGTH")]
+    pub default_string_length: Option<usize>,
+
+    /// How CHAR members and arrays of CHAR are converted, defaults to SINT with the ASCII radix
+    #[arg(long, value_enum, env = "UDTCONV_CHARS")]
+    pub chars: Option<Chars>,
+
+    /// Settings for a Studio 5000 version: the software revision, `--target-logix` replacing
+    /// the types the version lacks and `--bit-strings`. Names are limited to 40 characters
+    /// in every version and not part of a preset. Other options override them
+    #[arg(long, value_enum, env = "UDTCONV_PRESET")]
+    pub preset: Option<Preset>,
+
+    /// Studio 5000 version written to the L5X, such as `33.00`, which should match
+    /// the project it is imported into. Defaults to 35.0
+    #[arg(
+        long,
+        value_name = "REVISION",
+        env = "UDTCONV_SOFTWARE_REVISION",
+        value_parser = software_revision
+    )]
+    pub software_revision: Option<String>,
+
+    /// Revision of the L5X schema written to the L5X, defaults to 1.0.
+    /// The L5X always follows schema 1.0, other revisions are warned about
+    #[arg(
+        long,
+        value_name = "REVISION",
+        env = "UDTCONV_SCHEMA_REVISION",
+        value_parser = schema_revision
+    )]
+    pub schema_revision: Option<String>,
+
+    /// Name of the controller the L5X claims to be exported from, defaults to UdtConverter
+    #[arg(long, value_name = "NAME", env = "UDTCONV_CONTROLLER_NAME")]
+    pub controller_name: Option<String>,
+
+    /// Use the UDT with this Logix name as the target of the L5X instead of the last one.
+    /// Other names are written as the TargetName as given
+    #[arg(long, value_name = "NAME", env = "UDTCONV_TARGET_NAME")]
+    pub target_name: Option<String>,
+
+    /// Make every UDT a target of the L5X instead of the context of the last one,
+    /// so a whole library is imported at once
+    #[arg(long, conflicts_with = "split_output", env = "UDTCONV_ALL_TARGETS")]
+    pub all_targets: bool,
+
+    /// How BOOL members are stored, defaults to packing them into bits of hidden SINTs
+    #[arg(long, value_enum, env = "UDTCONV_BOOL_PACKING")]
+    pub bool_packing: Option<BoolPacking>,
+
+    /// Emit BOOL members as plain BOOLs without hidden members, same as `--bool-packing none`
+    #[arg(long, conflicts_with = "bool_packing", env = "UDTCONV_NO_BOOL_PACKING")]
+    pub no_bool_packing: bool,
+
+    /// Whether BYTE, WORD, DWORD and LWORD become unsigned or signed integers,
+    /// defaults to unsigned
+    #[arg(long, value_enum, env = "UDTCONV_BIT_STRINGS")]
+    pub bit_strings: Option<BitStrings>,
+
+    /// How timer and counter system types such as IEC_TIMER are converted,
+    /// defaults to the predefined TIMER and COUNTER of Logix
+    #[arg(long, value_enum, env = "UDTCONV_SYSTEM_TYPES")]
+    pub system_types: Option<SystemTypes>,
+
+    /// How DTL members are converted, defaults to LDT
+    #[arg(long, value_enum, env = "UDTCONV_DTL")]
+    pub dtl: Option<Dtl>,
+
+    /// How arrays with a lower bound other than 0 are converted, defaults to shifting
+    /// the indexes to start at 0
+    #[arg(long, value_enum, env = "UDTCONV_LOWER_BOUNDS")]
+    pub lower_bounds: Option<LowerBounds>,
+
+    /// How BOOL arrays that are not a multiple of 32 elements are converted, defaults to
+    /// rounding the element count up
+    #[arg(long, value_enum, env = "UDTCONV_BOOL_ARRAYS")]
+    pub bool_arrays: Option<BoolArrays>,
+
+    /// Language of the comments and titles to use as descriptions, such as `en-US` or `de`,
+    /// for SimaticML documents with several languages. Falls back to the first language
+    #[arg(long, value_name = "LANG", env = "UDTCONV_COMMENT_LANGUAGE")]
+    pub comment_language: Option<String>,
+
+    /// How members referencing a type that is neither elementary nor defined in the input
+    /// are converted, defaults to keeping the type name with a warning
+    #[arg(long, value_enum, env = "UDTCONV_UNKNOWN_TYPES")]
+    pub unknown_types: Option<UnknownTypes>,
+
+    /// Byte layout of the generated UDTs, defaults to the layout of Logix. `comms` pads them
+    /// to the layout of non-optimized S7 memory, for exchanging them as raw data, and packs
+    /// BOOLs contiguously, so it cannot be combined with another `--bool-packing`
+    #[arg(long, value_enum, env = "UDTCONV_LAYOUT")]
+    pub layout: Option<Layout>,
+
+    /// Major version of the Logix firmware to convert for, such as 30. Versions without
+    /// 64-bit types get a pair of DINTs for LINT, ULINT and LDT, and REAL for LREAL
+    #[arg(long, value_name = "VERSION", env = "UDTCONV_TARGET_LOGIX")]
+    pub target_logix: Option<u32>,
+
+    /// External access of members without accessibility attributes such as ExternalWritable,
+    /// defaults to read-write
+    #[arg(long, value_enum, env = "UDTCONV_DEFAULT_EXTERNAL_ACCESS")]
+    pub default_external_access: Option<ExternalAccess>,
+
+    /// Replace members typed VARIANT, ANY or REF_TO with this Logix type, such as DINT,
+    /// instead of failing the conversion
+    #[arg(long, value_name = "TYPE", env = "UDTCONV_PLACEHOLDER_TYPE")]
+    pub placeholder_type: Option<String>,
+
+    /// Fail on any construct that cannot be parsed,
+    /// instead of skipping it with a warning
+    #[arg(long, env = "UDTCONV_STRICT")]
+    pub strict: bool,
+
+    /// Don't report warnings with this code, such as `bit-string`, can be repeated
+    /// or comma separated
+    #[arg(
+        long,
+        value_name = "CODE",
+        env = "UDTCONV_ALLOW",
+        value_delimiter = ','
+    )]
+    pub allow: Vec<String>,
+
+    /// Fail if a UDT has no VERSION
+    #[arg(long, env = "UDTCONV_REQUIRE_VERSION")]
+    pub require_version: bool,
+
+    /// Add the VERSION of every UDT to its description, such as `TIA Portal version 0.1`
+    #[arg(long, env = "UDTCONV_VERSION_IN_DESCRIPTION")]
+    pub version_in_description: bool,
+
+    /// Convert LREAL members to REAL with a warning, for controllers and HMI drivers
+    /// that cannot handle LREAL
+    #[arg(long, env = "UDTCONV_LREAL_AS_REAL")]
+    pub lreal_as_real: bool,
+
+    /// Note the TIA Portal type in the description of members converted to another type,
+    /// such as WORD members that become UINT
+    #[arg(long, env = "UDTCONV_ORIGINAL_TYPES")]
+    pub original_types: bool,
+
+    /// Write this export date instead of the current time, such as `Mon Jan 01 00:00:00 2024`
+    #[arg(long, value_name = "DATE", env = "UDTCONV_EXPORT_DATE")]
+    pub export_date: Option<String>,
+
+    /// Leave out the export date, so converting the same input always gives the same L5X
+    #[arg(long, conflicts_with = "export_date", env = "UDTCONV_NO_EXPORT_DATE")]
+    pub no_export_date: bool,
+
+    /// Validate the generated L5X against this XSD, such as the one installed with
+    /// Studio 5000, and fail with the violations instead of writing it. Needs `xmllint`
+    #[arg(long, value_name = "FILE", env = "UDTCONV_SCHEMA")]
+    pub schema: Option<PathBuf>,
+
+    /// Character to indent the L5X with, defaults to spaces
+    #[arg(long, value_enum, env = "UDTCONV_INDENT_CHAR")]
+    pub indent_char: Option<IndentChar>,
+
+    /// Number of indent characters per level, defaults to 4
+    #[arg(long, value_name = "N", env = "UDTCONV_INDENT_WIDTH")]
+    pub indent_width: Option<usize>,
+
+    /// Write the L5X on a single line, for smaller files
+    #[arg(long, conflicts_with_all = ["indent_char", "indent_width"], env = "UDTCONV_COMPACT")]
+    pub compact: bool,
+
+    /// End the lines of the L5X with CR LF, like the files exported by Studio 5000
+    #[arg(long, env = "UDTCONV_CRLF")]
+    pub crlf: bool,
+
+    /// Start the L5X with a UTF-8 byte order mark and declare the encoding,
+    /// for Logix Designer versions and tools that expect one
+    #[arg(long, env = "UDTCONV_BOM")]
+    pub bom: bool,
+
+    /// Pick the UDTs to emit and the target UDT from a list,
+    /// instead of emitting all of them with the last one as the target
+    #[arg(long, conflicts_with = "watch", env = "UDTCONV_INTERACTIVE")]
+    pub interactive: bool,
+
+    /// Parse and generate the L5X without writing anything,
+    /// failing if the conversion fails or produces warnings
+    #[arg(long, env = "UDTCONV_CHECK")]
+    pub check: bool,
+}
+
+#[derive(Args)]
+pub struct InputArgs {
+    /// UDT file to use as input, or `-` for stdin
+    #[arg(value_name = "INPUT")]
+    pub input: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ManifestArgs {
+    /// Manifest with one `[[job]]` table per conversion, holding its input, output and options
+    #[arg(value_name = "MANIFEST")]
+    pub manifest: PathBuf,
+}
+
+/// Parse a `SIEMENS=ROCKWELL` type mapping
+fn parse_mapping(mapping: &str) -> Result<(String, String), String> {
+    match mapping.split_once('=') {
+        Some((siemens, rockwell)) if !siemens.trim().is_empty() && !rockwell.trim().is_empty() => {
+            Ok((siemens.trim().to_string(), rockwell.trim().to_string()))
+        }
+        _ => Err(format!("expected `SIEMENS=ROCKWELL`, got `{}`", mapping)),
+    }
+}
+
+impl ConvertArgs {
+    /// Get the input path, which is always present once the config file has been applied
+    pub fn input(&self) -> &Path {
+        self.input
+            .as_deref()
+            .expect("input is resolved before converting")
+    }
+}
+
+impl Cli {
+    /// Get the subcommand to run, falling back to `convert` when none was given
+    pub fn into_command(self) -> Command {
+        match (self.command, self.convert) {
+            (Some(command), _) => command,
+            (None, convert) => Command::Convert(convert.unwrap_or_default()),
+        }
+    }
+}
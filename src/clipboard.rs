@@ -0,0 +1,37 @@
+use crate::error::Error;
+
+/// Path used in messages to refer to the clipboard
+pub const CLIPBOARD_PATH: &str = "<clipboard>";
+
+/// Read the text on the clipboard
+#[cfg(feature = "clipboard")]
+pub fn read() -> Result<String, Error> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|err| Error::Io(format!("Failed to read the clipboard: {}", err)))
+}
+
+/// Replace the content of the clipboard with the text
+#[cfg(feature = "clipboard")]
+pub fn write(text: &str) -> Result<(), Error> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|err| Error::Io(format!("Failed to write the clipboard: {}", err)))
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn read() -> Result<String, Error> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn write(_text: &str) -> Result<(), Error> {
+    Err(unsupported())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn unsupported() -> Error {
+    Error::Usage(
+        "This build has no clipboard support, rebuild with `--features clipboard`".to_string(),
+    )
+}
@@ -0,0 +1,652 @@
+use crate::error::Error;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Config file used when none is given on the command line, if it exists
+pub const DEFAULT_CONFIG_PATH: &str = "udtconv.toml";
+
+/// How individual BOOL members are stored in the generated UDT
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoolPacking {
+    /// Pack BOOLs into bits of hidden SINT members, like Studio 5000 does internally
+    #[default]
+    Sint,
+    /// Pack BOOLs into bits of hidden DINT members, 32 to a member
+    Dint,
+    /// Pack only consecutive BOOLs into the same hidden SINT, placed where the BOOLs are,
+    /// to mirror the byte layout of the Siemens type
+    Contiguous,
+    /// Emit BOOLs as plain BOOL members and let Studio 5000 pack them on import
+    None,
+}
+
+impl BoolPacking {
+    /// Get the type of the hidden members BOOLs are packed into and its number of bits
+    pub fn container(self) -> Option<(&'static str, usize)> {
+        match self {
+            BoolPacking::Sint | BoolPacking::Contiguous => Some(("SINT", 8)),
+            BoolPacking::Dint => Some(("DINT", 32)),
+            BoolPacking::None => None,
+        }
+    }
+}
+
+/// How the interface of an FB or FC is converted
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterfaceLayout {
+    /// One UDT per section, such as inputs and outputs
+    #[default]
+    Sections,
+    /// One UDT with the members of all sections
+    Merged,
+    /// An Add-On Instruction skeleton per block, with the inputs, outputs and in-outs
+    /// as parameters and the static and temporary members as local tags
+    Aoi,
+    /// No UDTs, only the data types of a source are converted
+    Skip,
+}
+
+/// How WSTRING and WCHAR members are converted
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum WideStrings {
+    /// WSTRING becomes STRING or STRING_N and WCHAR becomes SINT, narrowing every character
+    #[default]
+    String,
+    /// WCHAR becomes INT and WSTRING an INT array holding its length words and characters
+    IntArray,
+}
+
+/// How CHAR members and arrays of CHAR are converted. Logix has no character type
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Chars {
+    /// SINT with the ASCII radix
+    #[default]
+    Ascii,
+    /// Arrays of CHAR become a STRING_N of the array length, single CHARs SINT with the ASCII radix
+    String,
+    /// SINT with the decimal radix
+    Sint,
+}
+
+/// How STRING members without a length are converted. They hold 254 characters in TIA Portal
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum DefaultString {
+    /// The STRING of Logix, which holds 82 characters
+    #[default]
+    Logix,
+    /// A STRING_N of the default string length
+    Sized,
+    /// Fail the conversion, requiring an explicit length in TIA Portal
+    Fail,
+}
+
+/// How arrays with a lower bound other than 0, such as `Array[1..16]`, are converted.
+/// Logix arrays always start at index 0
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum LowerBounds {
+    /// Keep the element count and start at 0, shifting every index
+    #[default]
+    Rebase,
+    /// Keep the indexes by adding unused elements below the lower bound
+    Pad,
+    /// Fail the conversion
+    Fail,
+}
+
+/// Character the L5X is indented with
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum IndentChar {
+    #[default]
+    Space,
+    Tab,
+}
+
+impl IndentChar {
+    /// Get the byte written for one level of indentation
+    pub fn as_byte(self) -> u8 {
+        match self {
+            IndentChar::Space => b' ',
+            IndentChar::Tab => b'\t',
+        }
+    }
+}
+
+/// Access of HMIs and other external devices to a member
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExternalAccess {
+    #[default]
+    ReadWrite,
+    ReadOnly,
+    None,
+}
+
+impl ExternalAccess {
+    /// Get the name of the access in an L5X
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExternalAccess::ReadWrite => "Read/Write",
+            ExternalAccess::ReadOnly => "Read Only",
+            ExternalAccess::None => "None",
+        }
+    }
+}
+
+/// How Studio 5000 displays the value of a member
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Radix {
+    Decimal,
+    Hex,
+    Binary,
+    Octal,
+    Ascii,
+    Float,
+    Exponential,
+}
+
+impl Radix {
+    /// Get the name of the radix in an L5X
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Radix::Decimal => "Decimal",
+            Radix::Hex => "Hex",
+            Radix::Binary => "Binary",
+            Radix::Octal => "Octal",
+            Radix::Ascii => "ASCII",
+            Radix::Float => "Float",
+            Radix::Exponential => "Exponential",
+        }
+    }
+}
+
+/// How DTL date and time members are converted
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dtl {
+    /// The LDT of Logix
+    #[default]
+    Ldt,
+    /// A generated DTL UDT with the YEAR, MONTH, DAY and other fields of the Siemens type,
+    /// for controllers without LDT
+    Udt,
+}
+
+/// Byte layout of the generated UDTs
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// The members in their order, laid out by Logix
+    #[default]
+    Logix,
+    /// Hidden padding members placing every member at its byte offset in the non-optimized
+    /// memory of an S7, for exchanging the UDTs as raw data. BOOLs are packed contiguously
+    Comms,
+}
+
+/// Settings of a single member, overriding the conversion
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MemberOverride {
+    /// Logix data type of the member, instead of the converted type
+    #[serde(rename = "type")]
+    pub data_type: Option<String>,
+    /// Radix of the member
+    pub radix: Option<Radix>,
+    /// External access of the member, instead of the one from its attributes
+    pub external_access: Option<ExternalAccess>,
+}
+
+/// Settings for a Studio 5000 version. Only the software revision, the target version and the
+/// conversion of bit strings differ between versions, naming limits are the same for all
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// Version 24, without unsigned and 64-bit integers or LREAL
+    V24,
+    /// Version 33
+    V33,
+    /// Version 36
+    V36,
+}
+
+impl Preset {
+    /// Set the software revision, the target version, which decides the elementary types
+    /// members may use, and the conversion of bit strings
+    pub fn apply(self, config: &mut Config) {
+        let version = match self {
+            Preset::V24 => 24,
+            Preset::V33 => 33,
+            Preset::V36 => 36,
+        };
+        config.software_revision = format!("{}.00", version);
+        config.target_logix = Some(version);
+        config.bit_strings = if self == Preset::V24 {
+            BitStrings::Signed
+        } else {
+            BitStrings::Unsigned
+        };
+    }
+}
+
+/// How members referencing a type that is neither elementary nor defined are converted
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnknownTypes {
+    /// Keep the type name with a warning, the type must already exist in the project
+    #[default]
+    Pass,
+    /// Fail the conversion, listing the undefined types
+    Fail,
+    /// Add a stub UDT with a single DINT member for each undefined type
+    Stub,
+}
+
+/// How BOOL arrays whose element count is not a multiple of 32 are converted.
+/// Studio 5000 only allows BOOL arrays in multiples of 32
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoolArrays {
+    /// Round the element count up to the next multiple of 32
+    #[default]
+    Round,
+    /// Keep the declared element count, which Studio 5000 rounds up on import
+    Keep,
+    /// Convert to a DINT array holding the BOOLs as bits, 32 to an element
+    Dint,
+}
+
+/// Which integers the bit strings BYTE, WORD, DWORD and LWORD become
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum BitStrings {
+    /// USINT, UINT, UDINT and ULINT
+    #[default]
+    Unsigned,
+    /// SINT, INT, DINT and LINT, as used by older firmware and many existing programs
+    Signed,
+}
+
+/// How timer and counter system types, such as IEC_TIMER or CTU_INT, are converted
+#[derive(Deserialize, ValueEnum, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum SystemTypes {
+    /// The predefined TIMER and COUNTER structures of Logix
+    #[default]
+    Predefined,
+    /// A generated UDT with the members of the Siemens type
+    Udt,
+}
+
+/// Rules for names generated by the converter
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Naming {
+    /// Prefix of the hidden members holding packed BOOLs, `{prefix}` in `hidden-name`
+    pub hidden_prefix: String,
+    /// Name of the hidden members holding packed BOOLs, where `{prefix}` is the hidden prefix,
+    /// `{udt}` the name of the UDT and `{n}` the number of the hidden member.
+    /// The UDT name is shortened to keep the name within 40 characters
+    pub hidden_name: String,
+    /// Prefix added to the name of every converted UDT and every reference to it
+    pub type_prefix: String,
+    /// Suffix added to the name of every converted UDT and every reference to it
+    pub type_suffix: String,
+    /// Name given to UDTs and members named after a reserved word of Logix,
+    /// where `{name}` is the original name
+    pub reserved_name: String,
+    /// Name of the UDTs synthesized for nested STRUCT members,
+    /// where `{udt}` is the name of the parent UDT and `{member}` the name of the member
+    pub struct_name: String,
+    /// Name of the UDTs converted from the sections of an FB or FC interface,
+    /// where `{block}` is the name of the block and `{section}` the name of the section
+    pub section_name: String,
+}
+
+impl Default for Naming {
+    fn default() -> Naming {
+        Naming {
+            hidden_prefix: "ZZZZZZZZZZ".to_string(),
+            hidden_name: "{prefix}{udt}{n}".to_string(),
+            type_prefix: String::new(),
+            type_suffix: String::new(),
+            reserved_name: "{name}_R".to_string(),
+            struct_name: "{udt}_{member}".to_string(),
+            section_name: "{block}_{section}".to_string(),
+        }
+    }
+}
+
+/// Settings loaded from a TOML config file. Everything is optional,
+/// and values given on the command line take precedence
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Input file, directory or glob pattern used when `--input` is not given
+    pub input: Option<PathBuf>,
+    /// Output file or directory used when `--output` is not given
+    pub output: Option<PathBuf>,
+    /// Directory for output files named after their target UDT, used when no output is given
+    pub output_dir: Option<PathBuf>,
+    /// Siemens type names mapped to the Rockwell type to use instead of the built-in conversion,
+    /// such as `WORD = "INT"` or `MY_SENSOR = "AOI_Sensor"`. Mapped types must exist in the project
+    pub types: BTreeMap<String, String>,
+    /// Radix of members by `Udt.Member`, Siemens type or Logix type, such as `WORD = "hex"`
+    /// or `"Motor.Status" = "binary"`, instead of the radix chosen from the data type
+    pub radix: BTreeMap<String, Radix>,
+    /// How individual BOOL members are stored
+    pub bool_packing: BoolPacking,
+    /// Rules for names generated by the converter
+    pub naming: Naming,
+    /// Studio 5000 version the L5X is generated for
+    pub software_revision: String,
+    /// Revision of the L5X schema the document claims, warned about unless it is one the
+    /// writer follows
+    pub schema_revision: String,
+    /// Name of the controller the L5X is exported from
+    pub controller_name: String,
+    /// UDT to use as the target of the L5X instead of the last one, by its Logix name
+    pub target_name: Option<String>,
+    /// Make every UDT a target of the L5X, to import all of them at once
+    pub all_targets: bool,
+    /// Fixed export date written instead of the current time, such as `Mon Jan 01 00:00:00 2024`
+    pub export_date: Option<String>,
+    /// Leave out the export date, so converting the same input always gives the same L5X
+    pub no_export_date: bool,
+    /// XSD of the L5X format, such as the one installed with Studio 5000,
+    /// to validate the generated documents against before writing them
+    pub schema: Option<PathBuf>,
+    /// Character the L5X is indented with
+    pub indent_char: IndentChar,
+    /// Number of indent characters per level
+    pub indent_width: usize,
+    /// Write the L5X on a single line without indentation
+    pub compact: bool,
+    /// End the lines of the L5X with CR LF like Studio 5000 does
+    pub crlf: bool,
+    /// Start the L5X with a UTF-8 byte order mark and declare its encoding
+    pub bom: bool,
+    /// Wildcard patterns of the UDT names to convert, all UDTs are converted if empty
+    pub only: Vec<String>,
+    /// Wildcard patterns of UDT names not to convert
+    pub exclude: Vec<String>,
+    /// UDT files, directories or glob patterns with UDTs the input may reference
+    pub library: Vec<PathBuf>,
+    /// Add the referenced library UDTs to the L5X, otherwise they are only reported
+    pub auto_include: bool,
+    /// Siemens UDT names (`Udt`) and member names (`Udt.Member`) mapped to their Logix names
+    pub rename: BTreeMap<String, String>,
+    /// CSV or TOML file with more renames, see `rename`
+    pub rename_map: Option<PathBuf>,
+    /// Settings of single members by `Udt.Member`, using the Siemens names,
+    /// such as `"Motor.Speed" = { type = "INT", radix = "hex" }`
+    pub members: BTreeMap<String, MemberOverride>,
+    /// Values of the global constants used as array bounds
+    pub constants: BTreeMap<String, isize>,
+    /// Add a controller tag for every global DB, with decorated data holding the start values
+    /// after `BEGIN` or the initial values of its members
+    pub db_tags: bool,
+    /// Add a controller tag of the target UDT
+    pub emit_tags: bool,
+    /// Name of the tag added by `emit-tags`, the name of the target UDT if unset
+    pub tag_name: Option<String>,
+    /// Program the tags are scoped to instead of the controller
+    pub program: Option<String>,
+    /// How the interface of an FB or FC is converted
+    pub interface: InterfaceLayout,
+    /// Fail if a UDT has no VERSION
+    pub require_version: bool,
+    /// Add the VERSION of every UDT to its description
+    pub version_in_description: bool,
+    /// Convert LREAL members to REAL, losing precision
+    pub lreal_as_real: bool,
+    /// Note the TIA Portal type in the description of members converted to another type
+    pub original_types: bool,
+    /// How WSTRING and WCHAR members are converted
+    pub wide_strings: WideStrings,
+    /// How STRING members without a length are converted
+    pub default_string: DefaultString,
+    /// How CHAR members and arrays of CHAR are converted
+    pub chars: Chars,
+    /// Length of the STRING_N used for STRING members without a length
+    pub default_string_length: usize,
+    /// Which integers BYTE, WORD, DWORD and LWORD become
+    pub bit_strings: BitStrings,
+    /// How timer and counter system types are converted
+    pub system_types: SystemTypes,
+    /// How DTL members are converted
+    pub dtl: Dtl,
+    /// How arrays with a lower bound other than 0 are converted
+    pub lower_bounds: LowerBounds,
+    /// How BOOL arrays that are not a multiple of 32 elements are converted
+    pub bool_arrays: BoolArrays,
+    /// How members referencing undefined types are converted
+    pub unknown_types: UnknownTypes,
+    /// Byte layout of the generated UDTs
+    pub layout: Layout,
+    /// Major version of the Logix firmware the UDTs are for, older versions get
+    /// 32-bit replacements of the 64-bit types
+    pub target_logix: Option<u32>,
+    /// External access of members without accessibility attributes
+    pub default_external_access: ExternalAccess,
+    /// Fail on constructs that cannot be parsed instead of skipping them with a warning
+    pub strict: bool,
+    /// Codes of the warnings not to report
+    pub allow: Vec<String>,
+    /// Language of the comments used as descriptions, such as `en-US`, for SimaticML documents
+    /// with comments in several languages
+    pub comment_language: Option<String>,
+    /// Logix type replacing VARIANT, ANY and REF_TO members, which fail the conversion if unset
+    pub placeholder_type: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            input: None,
+            output: None,
+            output_dir: None,
+            types: BTreeMap::new(),
+            radix: BTreeMap::new(),
+            bool_packing: BoolPacking::default(),
+            naming: Naming::default(),
+            software_revision: "35.0".to_string(),
+            schema_revision: "1.0".to_string(),
+            controller_name: "UdtConverter".to_string(),
+            target_name: None,
+            all_targets: false,
+            export_date: None,
+            no_export_date: false,
+            schema: None,
+            indent_char: IndentChar::Space,
+            indent_width: 4,
+            compact: false,
+            crlf: false,
+            bom: false,
+            only: vec![],
+            exclude: vec![],
+            library: vec![],
+            auto_include: true,
+            rename: BTreeMap::new(),
+            rename_map: None,
+            members: BTreeMap::new(),
+            constants: BTreeMap::new(),
+            db_tags: false,
+            emit_tags: false,
+            tag_name: None,
+            program: None,
+            interface: InterfaceLayout::default(),
+            require_version: false,
+            version_in_description: false,
+            original_types: false,
+            lreal_as_real: false,
+            wide_strings: WideStrings::default(),
+            default_string: DefaultString::default(),
+            chars: Chars::default(),
+            default_string_length: 254,
+            bit_strings: BitStrings::default(),
+            system_types: SystemTypes::default(),
+            dtl: Dtl::default(),
+            lower_bounds: LowerBounds::default(),
+            bool_arrays: BoolArrays::default(),
+            placeholder_type: None,
+            comment_language: None,
+            default_external_access: ExternalAccess::default(),
+            unknown_types: UnknownTypes::default(),
+            layout: Layout::default(),
+            target_logix: None,
+            strict: false,
+            allow: vec![],
+        }
+    }
+}
+
+impl Config {
+    /// Get the BOOL packing, which is contiguous for the comms layout
+    pub fn packing(&self) -> BoolPacking {
+        if self.layout == Layout::Comms {
+            BoolPacking::Contiguous
+        } else {
+            self.bool_packing
+        }
+    }
+
+    /// Get the type mapping override for a Siemens type, ignoring case
+    pub fn type_override(&self, siemens_type: &str) -> Option<&str> {
+        self.types
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(siemens_type))
+            .map(|(_, rockwell_type)| rockwell_type.as_str())
+    }
+
+    /// Get the settings of a member of a UDT, ignoring case
+    pub fn member_override(&self, udt: &str, member: &str) -> Option<&MemberOverride> {
+        let key = format!("{}.{}", udt, member);
+        self.members
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&key))
+            .map(|(_, settings)| settings)
+    }
+
+    /// Get the radix override of the first of the keys that has one, ignoring case
+    pub fn radix_override(&self, keys: &[&str]) -> Option<Radix> {
+        keys.iter().find_map(|key| {
+            self.radix
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, radix)| *radix)
+        })
+    }
+
+    /// Get the value of a global constant, ignoring case
+    pub fn constant(&self, name: &str) -> Option<isize> {
+        self.constants
+            .iter()
+            .find(|(constant, _)| constant.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Major versions of RSLogix 5000 and Studio 5000 that were released
+const LOGIX_VERSIONS: [u32; 23] = [
+    13, 14, 15, 16, 17, 18, 19, 20, 21, 24, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38,
+];
+
+/// Check a software revision such as `33.00` or `36`, returning it as `major.minor`
+pub fn software_revision(revision: &str) -> Result<String, String> {
+    let (major, minor) = revision
+        .trim()
+        .split_once('.')
+        .unwrap_or((revision.trim(), "00"));
+    let valid_minor = !minor.is_empty() && minor.chars().all(|c| c.is_ascii_digit());
+
+    match major.parse::<u32>() {
+        Ok(major) if LOGIX_VERSIONS.contains(&major) && valid_minor => {
+            Ok(format!("{}.{}", major, minor))
+        }
+        Ok(major) if valid_minor => Err(format!(
+            "there is no Logix version {}, expected one of {}",
+            major,
+            LOGIX_VERSIONS.map(|version| version.to_string()).join(", ")
+        )),
+        _ => Err(format!(
+            "expected a revision such as `33.00`, got `{}`",
+            revision
+        )),
+    }
+}
+
+/// Check a schema revision such as `1.0`
+pub fn schema_revision(revision: &str) -> Result<String, String> {
+    let numeric = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+
+    match revision.trim().split_once('.') {
+        Some((major, minor)) if numeric(major) && numeric(minor) => Ok(revision.trim().to_string()),
+        _ => Err(format!(
+            "expected a revision such as `1.0`, got `{}`",
+            revision
+        )),
+    }
+}
+
+/// Load the config file at the given path, or the default config file if it exists.
+/// Returns the default config when there is no config file
+pub fn load_config(path: Option<&Path>) -> Result<Config, Error> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG_PATH).is_file() => Path::new(DEFAULT_CONFIG_PATH),
+        None => return Ok(Config::default()),
+    };
+
+    let content = fs::read_to_string(path)
+        .map_err(|err| Error::Io(format!("Failed to read {}: {}", path.display(), err)))?;
+
+    log::debug!("Using config file {}", path.display());
+
+    let mut config: Config = toml::from_str(&content)
+        .map_err(|err| Error::Config(format!("Invalid config file {}: {}", path.display(), err)))?;
+
+    // Paths in the config file are relative to the config file, not the working directory
+    let base = path.parent().unwrap_or(Path::new(""));
+    config.input = config.input.map(|input| relative_to(base, input));
+    config.output = config.output.map(|output| relative_to(base, output));
+    config.output_dir = config.output_dir.map(|dir| base.join(dir));
+    config.rename_map = config.rename_map.map(|map| base.join(map));
+    config.schema = config.schema.map(|schema| base.join(schema));
+    config.library = config.library.iter().map(|path| base.join(path)).collect();
+    config.schema_revision = schema_revision(&config.schema_revision).map_err(|err| {
+        Error::Config(format!(
+            "Invalid schema-revision in {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+    config.software_revision = software_revision(&config.software_revision).map_err(|err| {
+        Error::Config(format!(
+            "Invalid software-revision in {}: {}",
+            path.display(),
+            err
+        ))
+    })?;
+
+    Ok(config)
+}
+
+/// Make a path from the config file relative to the given base, leaving `-` for stdin/stdout alone
+pub fn relative_to(base: &Path, path: PathBuf) -> PathBuf {
+    if path == Path::new(crate::STDIO_PATH) {
+        path
+    } else {
+        base.join(path)
+    }
+}
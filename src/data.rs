@@ -0,0 +1,249 @@
+use chrono::NaiveDate;
+
+/// A start value converted to the form Logix stores in tag data
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    /// Number as written in the `Value` of decorated data
+    Number(String),
+    /// Text of a string, with the `$` escapes shared by TIA Portal and Logix
+    Text(String),
+}
+
+/// Get the width in bits and signedness of a Logix integer type
+fn integer_width(data_type: &str) -> Option<(u32, bool)> {
+    Some(match data_type.to_uppercase().as_str() {
+        "SINT" => (8, true),
+        "INT" => (16, true),
+        "DINT" => (32, true),
+        "LINT" => (64, true),
+        "USINT" => (8, false),
+        "UINT" => (16, false),
+        "UDINT" => (32, false),
+        "ULINT" => (64, false),
+        _ => return None,
+    })
+}
+
+/// Wrap an integer into the range of the type, so `16#FFFF` in an INT becomes -1
+fn wrap(value: i128, bits: u32, signed: bool) -> i128 {
+    let value = value & ((1i128 << bits) - 1);
+    if signed && value >= 1i128 << (bits - 1) {
+        value - (1i128 << bits)
+    } else {
+        value
+    }
+}
+
+/// Parse an integer literal such as `5`, `-5`, `16#FF`, `-16#FF`, `INT#16#FF`, `W#16#FF`
+/// or `2#1010_1010`. The sign may come before the radix or the digits
+fn parse_integer(literal: &str) -> Option<i128> {
+    let literal = literal.replace('_', "");
+    let parts: Vec<&str> = literal.split('#').collect();
+    let unsigned = |part: &str| part.trim_start_matches(['-', '+']).to_string();
+
+    let (radix, digits) = match parts[..] {
+        [.., radix, digits] if ["2", "8", "16"].contains(&unsigned(radix).as_str()) => {
+            (unsigned(radix).parse().ok()?, digits)
+        }
+        [.., digits] => (10, digits),
+        [] => return None,
+    };
+
+    let negative = parts.iter().any(|part| part.starts_with('-'));
+    let digits = digits.trim_start_matches(['-', '+']);
+    let value = i128::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Parse a real literal such as `1.5`, `-2.5E3` or `REAL#1.5`
+fn parse_real(literal: &str) -> Option<f64> {
+    let literal = literal.replace('_', "");
+    let number = literal.rsplit('#').next()?;
+    number.parse().ok()
+}
+
+/// Strip the type prefix of a date or time literal, such as `T#` or `TOD#`
+fn literal_body<'a>(literal: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    let (prefix, body) = literal.split_once('#')?;
+    prefixes
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(prefix))
+        .then_some(body)
+}
+
+/// Parse a duration such as `T#1h_30m`, `T#-5s` or `LT#2ms500us` into nanoseconds
+fn parse_duration(literal: &str) -> Option<i128> {
+    let body = literal_body(literal, &["T", "TIME", "LT", "LTIME", "S5T", "S5TIME"])?;
+    let body = body.replace('_', "").to_lowercase();
+    let (negative, mut rest) = match body.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, body.as_str()),
+    };
+
+    let mut total = 0f64;
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let (number, tail) = rest.split_at(number_end);
+        let unit_end = tail
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+
+        let nanoseconds = match unit {
+            "d" => 86_400e9,
+            "h" => 3_600e9,
+            "m" => 60e9,
+            "s" => 1e9,
+            "ms" => 1e6,
+            "us" => 1e3,
+            "ns" => 1.0,
+            _ => return None,
+        };
+        total += number.parse::<f64>().ok()? * nanoseconds;
+        rest = tail;
+    }
+
+    let total = total.round() as i128;
+    Some(if negative { -total } else { total })
+}
+
+/// Parse a time of day such as `TOD#12:30:00.5` into nanoseconds since midnight
+fn parse_time_of_day(literal: &str) -> Option<i128> {
+    let body = literal_body(literal, &["TOD", "TIME_OF_DAY", "LTOD", "LTIME_OF_DAY"])?;
+    let mut parts = body.split(':');
+    let hours: i128 = parts.next()?.parse().ok()?;
+    let minutes: i128 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next().unwrap_or("0").parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((hours * 3600 + minutes * 60) * 1_000_000_000 + (seconds * 1e9).round() as i128)
+}
+
+/// Parse a date such as `D#2024-01-31` into days since 1990-01-01, where S7 dates start
+fn parse_date(literal: &str) -> Option<i128> {
+    let body = literal_body(literal, &["D", "DATE"])?;
+    let date = NaiveDate::parse_from_str(body, "%Y-%m-%d").ok()?;
+    let epoch = NaiveDate::from_ymd_opt(1990, 1, 1)?;
+    Some((date - epoch).num_days() as i128)
+}
+
+/// Parse a quoted string or character literal such as `'text'` or `STRING#'text'`,
+/// returning the text between the quotes
+fn parse_text(literal: &str) -> Option<&str> {
+    let body = match literal.split_once('#') {
+        Some((prefix, body)) if !prefix.starts_with('\'') => body,
+        _ => literal,
+    };
+    body.strip_prefix('\'')?.strip_suffix('\'')
+}
+
+/// Count the characters of a text, with every `$` escape counting as one character
+pub fn text_length(text: &str) -> usize {
+    let mut chars = text.chars();
+    let mut length = 0;
+
+    while let Some(c) = chars.next() {
+        // Two hex digits are one character, any other escape is a single letter
+        if c == '$' && chars.next().is_some_and(|c| c.is_ascii_hexdigit()) {
+            chars.next();
+        }
+        length += 1;
+    }
+    length
+}
+
+/// Convert the start value of a member from its TIA Portal literal to the value Logix stores,
+/// given the TIA Portal type and the Logix type of the member.
+/// Returns None for values that cannot be converted
+pub fn logix_value(literal: &str, siemens_type: &str, data_type: &str) -> Option<Value> {
+    let literal = literal.trim();
+    let siemens_type = siemens_type.to_uppercase();
+    let data_type = data_type.to_uppercase();
+
+    if data_type == "STRING" || data_type.starts_with("STRING_") {
+        return parse_text(literal).map(|text| Value::Text(text.to_string()));
+    }
+
+    if data_type == "BOOL" || data_type == "BIT" {
+        return match literal.to_uppercase().rsplit('#').next()? {
+            "TRUE" | "1" => Some(Value::Number("1".to_string())),
+            "FALSE" | "0" => Some(Value::Number("0".to_string())),
+            _ => None,
+        };
+    }
+
+    if data_type == "REAL" || data_type == "LREAL" {
+        return parse_real(literal).map(|value| Value::Number(format!("{:?}", value)));
+    }
+
+    let (bits, signed) = integer_width(&data_type)?;
+    let value = match siemens_type.as_str() {
+        "TIME" | "S5TIME" => parse_duration(literal)? / 1_000_000,
+        "LTIME" => parse_duration(literal)?,
+        "TOD" | "TIME_OF_DAY" => parse_time_of_day(literal)? / 1_000_000,
+        "LTOD" | "LTIME_OF_DAY" => parse_time_of_day(literal)?,
+        "DATE" => parse_date(literal)?,
+        "CHAR" => match parse_text(literal) {
+            Some(text) => {
+                let mut chars = text.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() || !c.is_ascii() {
+                    return None;
+                }
+                c as i128
+            }
+            None => parse_integer(literal)?,
+        },
+        "DT" | "DATE_AND_TIME" | "LDT" | "DTL" | "WCHAR" => return None,
+        _ => parse_integer(literal)?,
+    };
+
+    Some(Value::Number(wrap(value, bits, signed).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_signed_typed_and_based_integers() {
+        assert_eq!(parse_integer("5"), Some(5));
+        assert_eq!(parse_integer("-5"), Some(-5));
+        assert_eq!(parse_integer("INT#-5"), Some(-5));
+        assert_eq!(parse_integer("16#FF"), Some(255));
+        assert_eq!(parse_integer("-16#10"), Some(-16));
+        assert_eq!(parse_integer("INT#-16#10"), Some(-16));
+        assert_eq!(parse_integer("W#16#FF"), Some(255));
+        assert_eq!(parse_integer("2#1010_1010"), Some(170));
+        assert_eq!(parse_integer("8#17"), Some(15));
+        assert_eq!(parse_integer("16#FG"), None);
+    }
+
+    #[test]
+    fn wraps_into_the_range_of_the_type() {
+        assert_eq!(wrap(0xFFFF, 16, true), -1);
+        assert_eq!(wrap(0x8000, 16, true), -32768);
+        assert_eq!(wrap(0x7FFF, 16, true), 32767);
+        assert_eq!(wrap(-1, 8, false), 255);
+        assert_eq!(wrap(256, 8, false), 0);
+        assert_eq!(wrap(-16, 16, true), -16);
+    }
+
+    #[test]
+    fn converts_start_values() {
+        let number = |value: &str| Some(Value::Number(value.to_string()));
+
+        assert_eq!(logix_value("-16#10", "Int", "INT"), number("-16"));
+        assert_eq!(logix_value("16#FFFF", "Int", "INT"), number("-1"));
+        assert_eq!(logix_value("W#16#FFFF", "Word", "UINT"), number("65535"));
+        assert_eq!(logix_value("T#1s_500ms", "Time", "DINT"), number("1500"));
+        assert_eq!(logix_value("TRUE", "Bool", "BOOL"), number("1"));
+        assert_eq!(
+            logix_value("'a$'b'", "String", "STRING"),
+            Some(Value::Text("a$'b".to_string()))
+        );
+        assert_eq!(logix_value("x", "Int", "INT"), None);
+    }
+}
@@ -0,0 +1,286 @@
+use crate::cli::MessageFormat;
+use crate::error::Error;
+use crate::logger::{paint, BLUE, RED, YELLOW};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Format of diagnostics and errors, set once at startup
+static MESSAGE_FORMAT: OnceLock<MessageFormat> = OnceLock::new();
+
+/// Set the format used to report diagnostics and errors
+pub fn set_message_format(format: MessageFormat) {
+    let _ = MESSAGE_FORMAT.set(format);
+}
+
+/// Check if diagnostics and errors are reported as JSON
+pub fn json_messages() -> bool {
+    MESSAGE_FORMAT.get() == Some(&MessageFormat::Json)
+}
+
+/// Byte offsets of a piece of text in the input, with the position of its start
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line of the start
+    pub line: usize,
+    /// 1-based column of the start, counted in characters
+    pub column: usize,
+}
+
+impl Span {
+    /// Create a span from byte offsets in the source
+    pub fn new(source: &str, start: usize, end: usize) -> Span {
+        let (line, column, _) = line_col(source, start);
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// Create a span from the start of this span to the end of another
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+/// Severity of a diagnostic
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The conversion cannot continue
+    Error,
+    /// The L5X can be generated, but may behave differently than the UDT or fail to import
+    Warning,
+}
+
+/// A problem found during conversion, optionally pointing at the offending input
+pub struct Diagnostic {
+    /// Stable identifier of the kind of problem, such as `undefined-type`
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub span: Option<Span>,
+    /// File the span points into, if it is not the file being reported on
+    pub file: Option<PathBuf>,
+    /// Suggested fix for the problem
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    /// Create a new warning with the given code and message
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            code,
+            severity: Severity::Warning,
+            message: message.into(),
+            span: None,
+            file: None,
+            help: None,
+        }
+    }
+
+    /// Create a new error with the given code and message
+    pub fn error(code: &'static str, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+            file: None,
+            help: None,
+        }
+    }
+
+    /// Point the diagnostic at a piece of the input
+    pub fn with_span(mut self, span: Option<Span>) -> Diagnostic {
+        self.span = span;
+        self
+    }
+
+    /// Set the file the span points into
+    pub fn with_file(mut self, file: Option<PathBuf>) -> Diagnostic {
+        self.file = file;
+        self
+    }
+
+    /// Suggest a fix for the problem
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+/// Location of a diagnostic in a JSON message
+#[derive(Serialize)]
+struct JsonSpan {
+    start: usize,
+    end: usize,
+    line: usize,
+    column: usize,
+}
+
+/// A diagnostic or error as reported with `--message-format json`
+#[derive(Serialize)]
+struct JsonMessage<'a> {
+    code: &'a str,
+    severity: Severity,
+    file: Option<String>,
+    span: Option<JsonSpan>,
+    message: &'a str,
+    help: Option<&'a str>,
+}
+
+impl JsonMessage<'_> {
+    /// Print the message as a single line of JSON on stderr
+    fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(json) => eprintln!("{}", json),
+            Err(err) => log::error!("Failed to serialize diagnostic: {}", err),
+        }
+    }
+}
+
+/// Get the 1-based line and column of a byte offset, along with the text of that line
+pub fn line_col(source: &str, offset: usize) -> (usize, usize, &str) {
+    let offset = offset.min(source.len());
+    let line_start = source[..offset].rfind('\n').map_or(0, |pos| pos + 1);
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |pos| offset + pos);
+    let line = source[..offset].matches('\n').count() + 1;
+    let col = source[line_start..offset].chars().count() + 1;
+
+    (
+        line,
+        col,
+        source[line_start..line_end].trim_end_matches('\r'),
+    )
+}
+
+/// Render the location and source line of a span with a caret under the offending text,
+/// in the style of rustc
+fn render_snippet(path: &Path, source: &str, span: Span, color: &str) -> String {
+    let (line, col, text) = line_col(source, span.start);
+    let gutter = " ".repeat(line.to_string().len());
+
+    // Only underline the part of the span on its first line
+    let width = source[span.start.min(source.len())..span.end.min(source.len())]
+        .lines()
+        .next()
+        .map_or(0, |text| text.chars().count())
+        .max(1);
+
+    format!(
+        "\n{gutter}{} {}:{}:{}\n{gutter} {}\n{} {} {}\n{gutter} {} {}{}",
+        paint("-->", BLUE),
+        path.display(),
+        line,
+        col,
+        paint("|", BLUE),
+        paint(&line.to_string(), BLUE),
+        paint("|", BLUE),
+        text,
+        paint("|", BLUE),
+        " ".repeat(col - 1),
+        paint(&"^".repeat(width), color),
+    )
+}
+
+/// Print all diagnostics for a file as JSON
+fn print_json(path: &Path, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        let span = diagnostic.span.map(|span| JsonSpan {
+            start: span.start,
+            end: span.end,
+            line: span.line,
+            column: span.column,
+        });
+
+        JsonMessage {
+            code: diagnostic.code,
+            severity: diagnostic.severity,
+            file: Some(
+                diagnostic
+                    .file
+                    .as_deref()
+                    .unwrap_or(path)
+                    .display()
+                    .to_string(),
+            ),
+            span,
+            message: &diagnostic.message,
+            help: diagnostic.help.as_deref(),
+        }
+        .print();
+    }
+}
+
+/// Report all diagnostics for a file, showing the offending source line when the source is known
+pub fn print_diagnostics(path: &Path, source: Option<&str>, diagnostics: &[Diagnostic]) {
+    if json_messages() {
+        return print_json(path, diagnostics);
+    }
+
+    for diagnostic in diagnostics {
+        let (level, color) = match diagnostic.severity {
+            Severity::Error => (log::Level::Error, RED),
+            Severity::Warning => (log::Level::Warn, YELLOW),
+        };
+
+        let help = diagnostic
+            .help
+            .as_ref()
+            .map(|help| format!("\n  {} help: {}", paint("=", BLUE), help))
+            .unwrap_or_default();
+
+        // The source is only known for spans in the file being reported on
+        let file = diagnostic.file.as_deref().unwrap_or(path);
+        let source = source.filter(|_| file == path);
+
+        match (source, diagnostic.span) {
+            (Some(source), Some(span)) => log::log!(
+                level,
+                "{}{}{}",
+                diagnostic.message,
+                render_snippet(file, source, span, color),
+                help
+            ),
+            (None, Some(span)) => log::log!(
+                level,
+                "{}:{}:{}: {}{}",
+                file.display(),
+                span.line,
+                span.column,
+                diagnostic.message,
+                help
+            ),
+            (_, None) => log::log!(level, "{}: {}{}", file.display(), diagnostic.message, help),
+        }
+    }
+}
+
+/// Report an error that stopped the conversion, of a single file if given
+pub fn print_error(path: Option<&Path>, err: &Error) {
+    if json_messages() {
+        JsonMessage {
+            code: err.kind(),
+            severity: Severity::Error,
+            file: path.map(|path| path.display().to_string()),
+            span: None,
+            message: &err.to_string(),
+            help: None,
+        }
+        .print();
+    } else if let Some(path) = path {
+        log::error!("{}: {}", path.display(), err);
+    } else {
+        log::error!("{}", err);
+    }
+}
@@ -0,0 +1,128 @@
+/// Characters of Windows-1252 for the bytes 0x80 to 0x9F, where it differs from Latin-1.
+/// Bytes unused by Windows-1252 keep their Latin-1 control character
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// Decode UTF-16 text from pairs of bytes, replacing invalid characters
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Decode Windows-1252 text, which TIA Portal uses for sources on western Windows systems
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            0x80..=0x9f => WINDOWS_1252[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        })
+        .collect()
+}
+
+/// Guess if text without a byte order mark is UTF-16 from the zero bytes of ASCII characters.
+/// Returns true for little endian, false for big endian
+fn utf16_without_bom(bytes: &[u8]) -> Option<bool> {
+    let (even, odd) = bytes.chunks_exact(2).fold((0, 0), |(even, odd), pair| {
+        (
+            even + usize::from(pair[0] == 0),
+            odd + usize::from(pair[1] == 0),
+        )
+    });
+    let half = bytes.len() / 4;
+
+    match (even, odd) {
+        (even, odd) if odd > half && even == 0 => Some(true),
+        (even, odd) if even > half && odd == 0 => Some(false),
+        _ => None,
+    }
+}
+
+/// Decode an input file, detecting UTF-8 with or without a byte order mark,
+/// UTF-16 LE and BE, and falling back to Windows-1252
+pub fn decode(bytes: &[u8]) -> String {
+    if let Some(bytes) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        log::debug!("Input is UTF-8 with a byte order mark");
+        String::from_utf8_lossy(bytes).into_owned()
+    } else if let Some(bytes) = bytes.strip_prefix(b"\xff\xfe") {
+        log::debug!("Input is UTF-16 LE");
+        decode_utf16(bytes, u16::from_le_bytes)
+    } else if let Some(bytes) = bytes.strip_prefix(b"\xfe\xff") {
+        log::debug!("Input is UTF-16 BE");
+        decode_utf16(bytes, u16::from_be_bytes)
+    } else if let Some(little_endian) = utf16_without_bom(bytes) {
+        // Checked before UTF-8, as ASCII text in UTF-16 is valid UTF-8 full of NULs
+        log::debug!("Input is UTF-16 without a byte order mark");
+        if little_endian {
+            decode_utf16(bytes, u16::from_le_bytes)
+        } else {
+            decode_utf16(bytes, u16::from_be_bytes)
+        }
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        text.to_string()
+    } else {
+        log::debug!("Input is not UTF-8, decoding it as Windows-1252");
+        decode_windows_1252(bytes)
+    }
+}
+
+/// Turn CRLF and lone CR line endings into LF, as TIA Portal exports may mix them
+pub fn normalize_line_endings(text: String) -> String {
+    if text.contains('\r') {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "TYPE \"Größe\"\r\nEND_TYPE";
+
+    fn utf16(text: &str, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        text.encode_utf16().flat_map(to_bytes).collect()
+    }
+
+    #[test]
+    fn decodes_utf8() {
+        assert_eq!(decode(TEXT.as_bytes()), TEXT);
+        assert_eq!(decode(&[b"\xef\xbb\xbf", TEXT.as_bytes()].concat()), TEXT);
+    }
+
+    #[test]
+    fn decodes_utf16_with_bom() {
+        let little = [&b"\xff\xfe"[..], &utf16(TEXT, u16::to_le_bytes)].concat();
+        let big = [&b"\xfe\xff"[..], &utf16(TEXT, u16::to_be_bytes)].concat();
+
+        assert_eq!(decode(&little), TEXT);
+        assert_eq!(decode(&big), TEXT);
+    }
+
+    #[test]
+    fn decodes_utf16_without_bom() {
+        assert_eq!(decode(&utf16(TEXT, u16::to_le_bytes)), TEXT);
+        assert_eq!(decode(&utf16(TEXT, u16::to_be_bytes)), TEXT);
+        assert_eq!(decode(&utf16("TYPE", u16::to_le_bytes)), "TYPE");
+    }
+
+    #[test]
+    fn falls_back_to_windows_1252() {
+        assert_eq!(decode(b"Gr\xf6\xdfe \x80 \x93x\x94"), "Größe € “x”");
+    }
+
+    #[test]
+    fn normalizes_line_endings() {
+        assert_eq!(
+            normalize_line_endings("a\r\nb\rc\n".to_string()),
+            "a\nb\nc\n"
+        );
+    }
+}
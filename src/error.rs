@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// Errors that stop a conversion. Each kind has a stable exit code so scripts
+/// can tell them apart, see `Error::exit_code`
+#[derive(Debug)]
+pub enum Error {
+    /// One or more files of a batch failed, each failure is reported separately
+    Batch(String),
+    /// The input could not be parsed as UDTs
+    Parse(String),
+    /// The input uses a construct that cannot be converted
+    Unsupported(String),
+    /// A file could not be read or written
+    Io(String),
+    /// The L5X document could not be generated
+    Xml(String),
+    /// `--check` or `validate` found warnings, or `--require-version` a UDT without VERSION
+    Check(String),
+    /// The config file is invalid
+    Config(String),
+    /// The command line arguments are invalid
+    Usage(String),
+}
+
+/// Exit code for a successful run
+pub const EXIT_SUCCESS: i32 = 0;
+
+/// Exit code for invalid command line arguments, also used for errors reported by clap
+pub const EXIT_USAGE: i32 = 64;
+
+impl Error {
+    /// Get the exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Batch(_) => 1,
+            Error::Parse(_) => 2,
+            Error::Unsupported(_) => 3,
+            Error::Io(_) => 4,
+            Error::Xml(_) => 5,
+            Error::Check(_) => 6,
+            Error::Config(_) => 7,
+            Error::Usage(_) => EXIT_USAGE,
+        }
+    }
+
+    /// Get a short name for the kind of error, used as its code in JSON messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Batch(_) => "batch",
+            Error::Parse(_) => "parse",
+            Error::Unsupported(_) => "unsupported",
+            Error::Io(_) => "io",
+            Error::Xml(_) => "xml",
+            Error::Check(_) => "check",
+            Error::Config(_) => "config",
+            Error::Usage(_) => "usage",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Batch(msg)
+            | Error::Parse(msg)
+            | Error::Unsupported(msg)
+            | Error::Io(msg)
+            | Error::Xml(msg)
+            | Error::Check(msg)
+            | Error::Config(msg)
+            | Error::Usage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
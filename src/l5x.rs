@@ -1,17 +1,45 @@
+use crate::config::{Config, ExternalAccess};
+use crate::data::{self, Value};
 use crate::udt;
 use chrono::Local;
-use quick_xml;
 use std::{io::Cursor, vec};
 
+/// Schema revisions whose elements and attributes the writer produces
+pub const SCHEMA_REVISIONS: [&str; 1] = ["1.0"];
+
+/// Check if a character is allowed in XML 1.0, which excludes most control characters
+fn xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{fffe}' && c != '\u{ffff}')
+}
+
+/// Create an element with the text as CDATA, like Studio 5000 writes it. Characters XML does
+/// not allow are removed, and text that would end the CDATA section early is escaped instead
+fn write_text_element(
+    name: &str,
+    attributes: &[(&str, &str)],
+    text: &str,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    let text: String = text.chars().filter(|&c| xml_char(c)).collect();
+    let element = writer
+        .create_element(name)
+        .with_attributes(attributes.iter().copied());
+
+    if text.contains("]]>") {
+        element.write_text_content(quick_xml::events::BytesText::new(&text))?;
+    } else {
+        element.write_cdata_content(quick_xml::events::BytesCData::new(text))?;
+    }
+    Ok(())
+}
+
 /// Create description element
-fn write_description(
+pub fn write_description(
     description: Option<&str>,
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
     if let Some(desc) = description {
-        writer
-            .create_element("Description")
-            .write_cdata_content(quick_xml::events::BytesCData::new(desc))?;
+        write_text_element("Description", &[], desc, writer)?;
     }
     Ok(())
 }
@@ -19,24 +47,15 @@ fn write_description(
 /// Write members to UDT element
 fn write_members(
     udt: &udt::Udt,
+    config: &Config,
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
     for member in &udt.members {
-        let bounds = (if let Some(dim) = member.array_bounds {
-            if member.data_type.to_uppercase() == "BOOL" {
-                ((dim.1 + 1) as usize).div_ceil(32) * 32
-            } else {
-                (dim.1 + 1) as usize
-            }
-        } else {
-            0
-        })
-        .to_string();
-
-        let data_type = if let (None, true) = (
-            member.array_bounds,
-            member.data_type.to_uppercase() == "BOOL",
-        ) {
+        // BOOL arrays are already sized after the BOOL array policy
+        let bounds = member.element_count().unwrap_or(0).to_string();
+
+        // BOOLs packed into a hidden member are bits of that member
+        let data_type = if member.target.is_some() {
             "BIT"
         } else {
             member.data_type.as_str()
@@ -44,12 +63,13 @@ fn write_members(
 
         let hidden = member.hidden.to_string();
 
-        let external_access = if member.external_write {
-            "Read/Write"
-        } else if member.external_read {
-            "Read Only"
+        // The members of a string type are accessible like those of the built-in STRING
+        let external_access = if udt.string {
+            ExternalAccess::ReadWrite.as_str()
         } else {
-            "None"
+            member
+                .external_access(config.default_external_access)
+                .as_str()
         };
 
         let bit_num = if let Some(bit) = member.bit_num {
@@ -58,10 +78,13 @@ fn write_members(
             "".to_string()
         };
 
-        let radix = if udt::numeric_type(data_type) {
+        // The characters of a string type. Strings are structures and have no radix
+        let radix = if udt.string && member.name == "DATA" {
+            "ASCII"
+        } else if udt.string {
             "Decimal"
-        } else if udt::char_type(data_type) {
-            "Char"
+        } else if udt::numeric_type(data_type) {
+            member.radix.map_or("Decimal", |radix| radix.as_str())
         } else {
             "NullType"
         };
@@ -75,149 +98,478 @@ fn write_members(
             ("ExternalAccess", external_access),
         ];
 
-        if let (None, true) = (
-            member.array_bounds,
-            member.data_type.to_uppercase() == "BOOL",
-        ) {
-            attributes.push(("Target", member.target.as_ref().unwrap().as_str()));
+        if let Some(target) = &member.target {
+            attributes.push(("Target", target.as_str()));
             attributes.push(("BitNumber", bit_num.as_str()))
         }
 
         writer
             .create_element("Member")
             .with_attributes(attributes)
-            .write_inner_content(|writer| write_description(member.description.as_deref(), writer))?;
+            .write_inner_content(|writer| {
+                write_description(member.description.as_deref(), writer)
+            })?;
     }
     Ok(())
 }
 
-/// Create a data type elements
-fn write_data_type(
+/// Create a data type elements, used as a target or as context, with the data types it depends on
+pub fn write_data_type(
     udt: &udt::Udt,
+    dependencies: &[&udt::Udt],
+    usage: &str,
+    config: &Config,
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
+    let family = if udt.string {
+        "StringFamily"
+    } else {
+        "NoFamily"
+    };
+
     writer
         .create_element("DataType")
         .with_attributes([
-            ("Use", "Target"),
+            ("Use", usage),
             ("Name", &udt.name),
-            ("Family", "NoFamily"),
+            ("Family", family),
             ("Class", "User"),
         ])
         .write_inner_content(|writer| {
-            write_description(udt.description.as_deref(), writer)?;
+            let description = if config.version_in_description {
+                udt.versioned_description()
+            } else {
+                udt.description.clone()
+            };
+            write_description(description.as_deref(), writer)?;
 
             writer
                 .create_element("Members")
-                .write_inner_content(|writer| write_members(udt, writer))?;
+                .write_inner_content(|writer| write_members(udt, config, writer))?;
+
+            let names = dependency_names(dependencies, udt);
+            if !names.is_empty() {
+                writer
+                    .create_element("Dependencies")
+                    .write_inner_content(|writer| write_dependencies(&names, writer))?;
+            }
             Ok::<_, quick_xml::Error>(())
         })?;
     Ok(())
 }
 
-/// Create dependancy elements
+/// Get the names of the UDTs a data type depends on and of the custom length strings
+/// its members use, including members that are arrays of strings
+fn dependency_names<'a>(udts: &[&'a udt::Udt], parent_udt: &'a udt::Udt) -> Vec<&'a str> {
+    let mut names: Vec<&str> = udts.iter().map(|udt| udt.name.as_str()).collect();
+
+    for member in &parent_udt.members {
+        let known = names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(&member.data_type));
+        if udt::custom_string_type(&member.data_type) && !known {
+            names.push(&member.data_type);
+        }
+    }
+    names
+}
+
+/// Create dependancy elements for the named data types
 fn write_dependencies(
-    udts: &Vec<udt::Udt>,
+    names: &[&str],
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
-    for udt in udts {
+    for name in names {
         writer
             .create_element("Dependency")
-            .with_attributes([("Type", "DataType"), ("Name", udt.name.as_str())])
+            .with_attributes([("Type", "DataType"), ("Name", name)])
             .write_empty()?;
     }
     Ok(())
 }
 
-/// Create elemnt for parent data type
-fn write_parent_data_type(
-    udts: &Vec<udt::Udt>,
-    parent_udt: udt::Udt,
+/// Create elements for all UDTs. The targets depend on the UDTs they use directly or
+/// through other UDTs, the other UDTs on the UDTs they use directly
+fn write_all_data_types(
+    udts: &[udt::Udt],
+    targets: &[udt::Udt],
+    config: &Config,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    let all_udts: Vec<udt::Udt> = udts.iter().chain(targets).cloned().collect();
+
+    for target in targets {
+        let dependencies = udt::all_dependencies(target, &all_udts);
+        write_data_type(target, &dependencies, "Target", config, writer)?;
+    }
+
+    for udt in udts {
+        let dependencies = udt::direct_dependencies(udt, &all_udts);
+        write_data_type(udt, &dependencies, "Target", config, writer)?;
+    }
+    Ok(())
+}
+
+/// Create data types element
+fn write_data_types(
+    udts: &[udt::Udt],
+    targets: &[udt::Udt],
+    config: &Config,
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
     writer
-        .create_element("DataType")
+        .create_element("DataTypes")
+        .with_attribute(("Use", "Context"))
+        .write_inner_content(|writer| write_all_data_types(udts, targets, config, writer))?;
+    Ok(())
+}
+
+/// Get the start value of an element of a member, 0 if it has none or it cannot be converted
+pub fn start_value(member: &udt::UdtMember, data_type: &str, index: usize) -> Value {
+    let siemens_type = member
+        .source
+        .as_ref()
+        .map_or(data_type, |source| source.name.as_str());
+
+    member
+        .default
+        .as_ref()
+        .and_then(|values| values.get(index))
+        .and_then(|value| data::logix_value(value, siemens_type, data_type))
+        .unwrap_or_else(|| Value::Number("0".to_string()))
+}
+
+/// Get the radix of a number in decorated data, which must match how its value is written
+pub fn data_radix(data_type: &str) -> Option<&'static str> {
+    match data_type.to_uppercase().as_str() {
+        "BOOL" => None,
+        "REAL" | "LREAL" => Some("Float"),
+        _ => Some("Decimal"),
+    }
+}
+
+/// Create the LEN and DATA members of a string with the given text
+fn write_string_data(
+    text: &str,
+    data_type: &str,
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    writer
+        .create_element("DataValueMember")
         .with_attributes([
-            ("Use", "Target"),
-            ("Name", &parent_udt.name),
-            ("Family", "NoFamily"),
-            ("Class", "User"),
+            ("Name", "LEN"),
+            ("DataType", "DINT"),
+            ("Radix", "Decimal"),
+            ("Value", data::text_length(text).to_string().as_str()),
         ])
-        .write_inner_content(|writer| {
-            write_description(parent_udt.description.as_deref(), writer)?;
+        .write_empty()?;
+    write_text_element(
+        "DataValueMember",
+        &[
+            ("Name", "DATA"),
+            ("DataType", data_type),
+            ("Radix", "ASCII"),
+        ],
+        &format!("'{}'", text),
+        writer,
+    )
+}
 
-            writer
-                .create_element("Members")
-                .write_inner_content(|writer| write_members(&parent_udt, writer))?;
+/// Create the data of one element of a member, named for members and indexed for
+/// array elements. Members of unknown or predefined structures are left out
+fn write_element_data(
+    member: &udt::UdtMember,
+    name: (&str, &str),
+    index: usize,
+    udts: &[udt::Udt],
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    let data_type = if member.target.is_some() {
+        "BOOL"
+    } else {
+        member.data_type.as_str()
+    };
+    let (element, structure) = if name.0 == "Index" {
+        ("Element", "Structure")
+    } else {
+        ("DataValueMember", "StructureMember")
+    };
 
+    if udt::numeric_type(data_type) {
+        let Value::Number(value) = start_value(member, data_type, index) else {
+            return Ok(());
+        };
+        let mut attributes = vec![name, ("DataType", data_type)];
+        if let Some(radix) = data_radix(data_type) {
+            attributes.push(("Radix", radix));
+        }
+        attributes.push(("Value", &value));
+        if element == "Element" {
+            attributes.retain(|(key, _)| *key == "Index" || *key == "Value");
+        }
+        writer
+            .create_element(element)
+            .with_attributes(attributes)
+            .write_empty()?;
+    } else if data_type.eq_ignore_ascii_case("STRING") || udt::custom_string_type(data_type) {
+        let text = match start_value(member, data_type, index) {
+            Value::Text(text) => text,
+            Value::Number(_) => String::new(),
+        };
+        let attributes = [name, ("DataType", data_type)];
+        let inner = |writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>| {
+            write_string_data(&text, data_type, writer)
+        };
+        if element == "Element" {
             writer
-                .create_element("Dependencies")
-                .write_inner_content(|writer| write_dependencies(udts, writer))?;
-            Ok::<_, quick_xml::Error>(())
-        })?;
+                .create_element(element)
+                .with_attributes([name])
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element(structure)
+                        .with_attributes([("DataType", data_type)])
+                        .write_inner_content(inner)?;
+                    Ok::<_, quick_xml::Error>(())
+                })?;
+        } else {
+            writer
+                .create_element(structure)
+                .with_attributes(attributes)
+                .write_inner_content(inner)?;
+        }
+    } else if let Some(udt) = udts
+        .iter()
+        .find(|udt| udt.name.eq_ignore_ascii_case(data_type))
+    {
+        let inner = |writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>| {
+            write_structure_data(udt, udts, writer)
+        };
+        if element == "Element" {
+            writer
+                .create_element(element)
+                .with_attributes([name])
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element(structure)
+                        .with_attributes([("DataType", udt.name.as_str())])
+                        .write_inner_content(inner)?;
+                    Ok::<_, quick_xml::Error>(())
+                })?;
+        } else {
+            writer
+                .create_element(structure)
+                .with_attributes([name, ("DataType", udt.name.as_str())])
+                .write_inner_content(inner)?;
+        }
+    } else {
+        log::debug!("No data written for {} of type {}", name.1, data_type);
+    }
     Ok(())
 }
 
-/// Create elements for all UDTs
-fn write_all_data_types(
-    udts: &Vec<udt::Udt>,
-    parent_udt: udt::Udt,
+/// Create the decorated data of the members of a UDT, holding their start values.
+/// Hidden members only exist in the DataType and are left out
+fn write_structure_data(
+    udt: &udt::Udt,
+    udts: &[udt::Udt],
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
-    write_parent_data_type(udts, parent_udt, writer)?;
+    for member in udt.members.iter().filter(|member| !member.hidden) {
+        let Some(count) = member.element_count() else {
+            write_element_data(member, ("Name", &member.name), 0, udts, writer)?;
+            continue;
+        };
 
-    for udt in udts {
-        write_data_type(&udt, writer)?;
+        let data_type = member.data_type.as_str();
+        let mut attributes = vec![("Name", member.name.as_str()), ("DataType", data_type)];
+        let dimensions = count.to_string();
+        attributes.push(("Dimensions", &dimensions));
+        if udt::numeric_type(data_type) {
+            attributes.extend(data_radix(data_type).map(|radix| ("Radix", radix)));
+        }
+
+        writer
+            .create_element("ArrayMember")
+            .with_attributes(attributes)
+            .write_inner_content(|writer| {
+                for index in 0..count {
+                    let name = format!("[{}]", index);
+                    write_element_data(member, ("Index", &name), index, udts, writer)?;
+                }
+                Ok::<_, quick_xml::Error>(())
+            })?;
     }
     Ok(())
 }
 
-/// Create data types element
-fn write_data_types(
-    udts: &Vec<udt::Udt>,
-    parent_udt: udt::Udt,
+/// Create the decorated data of a tag of the UDT, holding the start values of its members
+fn write_tag_data(
+    udt: &udt::Udt,
+    udts: &[udt::Udt],
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
     writer
-        .create_element("DataTypes")
+        .create_element("Data")
+        .with_attribute(("Format", "Decorated"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("Structure")
+                .with_attribute(("DataType", udt.name.as_str()))
+                .write_inner_content(|writer| write_structure_data(udt, udts, writer))?;
+            Ok::<_, quick_xml::Error>(())
+        })?;
+    Ok(())
+}
+
+/// Create tag elements with their names and UDTs, such as the UDTs holding the structure
+/// of a global DB, with the start values of their members as tag data
+fn write_tags(
+    tags: &[(String, &udt::Udt)],
+    udts: &[udt::Udt],
+    writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
+) -> Result<(), quick_xml::Error> {
+    writer
+        .create_element("Tags")
         .with_attribute(("Use", "Context"))
-        .write_inner_content(|writer| write_all_data_types(udts, parent_udt, writer))?;
+        .write_inner_content(|writer| {
+            for (name, udt) in tags {
+                writer
+                    .create_element("Tag")
+                    .with_attributes([
+                        ("Name", name.as_str()),
+                        ("TagType", "Base"),
+                        ("DataType", udt.name.as_str()),
+                        ("Constant", "false"),
+                        ("ExternalAccess", "Read/Write"),
+                    ])
+                    .write_inner_content(|writer| {
+                        write_description(udt.description.as_deref(), writer)?;
+                        write_tag_data(udt, udts, writer)
+                    })?;
+            }
+            Ok::<_, quick_xml::Error>(())
+        })?;
     Ok(())
 }
 
 /// Create controller element
 fn write_controller(
-    udts: &Vec<udt::Udt>,
-    parent_udt: udt::Udt,
+    udts: &[udt::Udt],
+    targets: &[udt::Udt],
+    config: &Config,
     writer: &mut quick_xml::Writer<Cursor<Vec<u8>>>,
 ) -> Result<(), quick_xml::Error> {
+    let all_udts: Vec<udt::Udt> = udts.iter().chain(targets).cloned().collect();
+    let mut tags: Vec<(String, &udt::Udt)> = all_udts
+        .iter()
+        .filter(|udt| config.db_tags && udt.data_block)
+        .map(|udt| (udt.name.clone(), udt))
+        .collect();
+
+    if let Some(parent_udt) = targets.last().filter(|_| config.emit_tags) {
+        let name = config
+            .tag_name
+            .clone()
+            .unwrap_or_else(|| parent_udt.name.clone());
+        if !tags.iter().any(|(tag, _)| tag.eq_ignore_ascii_case(&name)) {
+            tags.push((name, parent_udt));
+        }
+    }
+
     writer
         .create_element("Controller")
-        .with_attributes([("Use", "Context"), ("Name", "UdtConverter")])
-        .write_inner_content(|writer| write_data_types(udts, parent_udt, writer))?;
+        .with_attributes([
+            ("Use", "Context"),
+            ("Name", config.controller_name.as_str()),
+        ])
+        .write_inner_content(|writer| {
+            write_data_types(udts, targets, config, writer)?;
+
+            // Data types only exist in the controller scope, tags can be in a program
+            match &config.program {
+                Some(program) if !tags.is_empty() => {
+                    writer
+                        .create_element("Programs")
+                        .with_attribute(("Use", "Context"))
+                        .write_inner_content(|writer| {
+                            writer
+                                .create_element("Program")
+                                .with_attributes([("Use", "Context"), ("Name", program.as_str())])
+                                .write_inner_content(|writer| {
+                                    write_tags(&tags, &all_udts, writer)
+                                })?;
+                            Ok::<_, quick_xml::Error>(())
+                        })?;
+                }
+                _ if !tags.is_empty() => write_tags(&tags, &all_udts, writer)?,
+                _ => {}
+            }
+            Ok::<_, quick_xml::Error>(())
+        })?;
     Ok(())
 }
 
-/// Generates L5X file (stored in memory as Vec<u8>) for parsed UDTs
+/// Get the export date written to the root element, the current time unless one is given
+pub fn export_date(config: &Config) -> String {
+    match &config.export_date {
+        Some(date) => date.clone(),
+        None => Local::now().format("%a %b %d %H:%M:%S %Y").to_string(),
+    }
+}
+
+/// Create the writer of an L5X, indented as configured or on a single line
+pub fn new_writer(config: &Config) -> quick_xml::Writer<Cursor<Vec<u8>>> {
+    let buffer = Cursor::new(Vec::<u8>::new());
+    if config.compact {
+        quick_xml::Writer::new(buffer)
+    } else {
+        quick_xml::Writer::new_with_indent(
+            buffer,
+            config.indent_char.as_byte(),
+            config.indent_width,
+        )
+    }
+}
+
+/// Generates L5X file (stored in memory as Vec<u8>) with the targets and the other UDTs
+/// as their context. The last target names the document
 pub fn create_l5x(
-    udts: &Vec<udt::Udt>,
-    parent_udt: udt::Udt,
+    udts: &[udt::Udt],
+    targets: &[udt::Udt],
+    config: &Config,
 ) -> Result<quick_xml::Writer<Cursor<Vec<u8>>>, quick_xml::Error> {
-    let mut writer = quick_xml::Writer::new_with_indent(Cursor::new(Vec::<u8>::new()), b' ', 4);
+    let mut writer = new_writer(config);
+    let parent_udt = targets.last().expect("An L5X has at least one target");
+
+    let export_date = export_date(config);
+    // The UDT picked as the target keeps the case of its name
+    let target_name = match &config.target_name {
+        Some(name) if !name.eq_ignore_ascii_case(&parent_udt.name) => name.clone(),
+        _ => parent_udt.name.clone(),
+    };
+    let target_count = targets.len().to_string();
+    let mut attributes = vec![
+        ("SchemaRevision", config.schema_revision.as_str()),
+        ("SoftwareRevision", config.software_revision.as_str()),
+        ("TargetName", target_name.as_str()),
+        ("TargetType", "DataType"),
+        ("TargetCount", target_count.as_str()),
+        ("ContainsContext", "true"),
+        ("ExportDate", export_date.as_str()),
+        ("ExportOptions", "References NoRawData DecoratedData Context Dependencies ForceProtectedEncoding AllProjDocTrans"),
+    ];
+    if config.no_export_date {
+        attributes.retain(|(name, _)| *name != "ExportDate");
+    }
+    if targets.len() == 1 {
+        attributes.retain(|(name, _)| *name != "TargetCount");
+    }
 
     // Create root element
-    writer.create_element("RSLogix5000Content")
-        .with_attributes([
-            ("SchemaRevision", "1.0"), 
-            ("SoftwareRevision", "35.0"), 
-            ("TargetName", &parent_udt.name), 
-            ("TargetType", "DataType"), 
-            ("ContainsContext", "true"), 
-            ("ExportData", &Local::now().format("%a %b %d %H:%M:%S %Y").to_string()), 
-            ("ExportOptions", "References NoRawData L5KData DecoratedData Context Dependencies ForceProtectedEncoding AllProjDocTrans"), 
-        ]).write_inner_content(|writer| {
-            write_controller(udts, parent_udt, writer)
-        })?;
+    writer
+        .create_element("RSLogix5000Content")
+        .with_attributes(attributes)
+        .write_inner_content(|writer| write_controller(udts, targets, config, writer))?;
 
     Ok(writer)
 }
@@ -0,0 +1,369 @@
+use crate::config::Config;
+use crate::diagnostic::Diagnostic;
+use crate::udt::{SourceType, Udt, UdtMember};
+use std::collections::{BTreeMap, HashMap};
+
+/// Size and alignment of a data type in Logix, in bytes
+#[derive(Clone, Copy)]
+struct Placement {
+    size: usize,
+    align: usize,
+}
+
+/// Sizes of the UDTs laid out so far, by lowercase name. UDTs of an unknown size are missing
+#[derive(Default)]
+struct Sizes {
+    /// Size in bytes in the non-optimized memory of an S7
+    siemens: HashMap<String, usize>,
+    logix: HashMap<String, Placement>,
+}
+
+/// Round the offset up to a multiple of the alignment
+fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+/// Get the length of a STRING or WSTRING type, 254 if it has none
+fn string_length(name: &str, keyword: &str) -> Option<usize> {
+    let rest = name.strip_prefix(keyword)?;
+    if rest.is_empty() {
+        return Some(254);
+    }
+    rest.strip_prefix('[')?
+        .strip_suffix(']')?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Get the size in bytes of a TIA Portal elementary type other than BOOL,
+/// and whether it is a string, which is laid out like a STRUCT
+fn siemens_size(name: &str) -> Option<(usize, bool)> {
+    let name = name.to_uppercase();
+
+    if let Some(length) = string_length(&name, "WSTRING") {
+        return Some((2 * length + 4, true));
+    }
+    if let Some(length) = string_length(&name, "STRING") {
+        return Some((length + 2, true));
+    }
+
+    let size = match name.as_str() {
+        "BYTE" | "CHAR" | "SINT" | "USINT" => 1,
+        "WORD" | "INT" | "UINT" | "WCHAR" | "DATE" | "S5TIME" => 2,
+        "DWORD" | "DINT" | "UDINT" | "REAL" | "TIME" | "TOD" | "TIME_OF_DAY" => 4,
+        "LWORD" | "LINT" | "ULINT" | "LREAL" | "LTIME" | "LTOD" | "LTIME_OF_DAY" | "DT"
+        | "DATE_AND_TIME" | "LDT" => 8,
+        "DTL" => 12,
+        _ => return None,
+    };
+    Some((size, false))
+}
+
+/// Get the size and alignment in bits of a member in non-optimized memory.
+/// BOOLs take a bit, other types of a byte are byte aligned and everything else,
+/// including arrays, strings and STRUCTs, starts and ends on a word
+fn siemens_member(
+    member: &UdtMember,
+    source: &SourceType,
+    sizes: &Sizes,
+) -> Option<(usize, usize)> {
+    if source.name.eq_ignore_ascii_case("BOOL") {
+        return Some(match source.count {
+            None => (1, 1),
+            Some(count) => (align_up(count, 16), 16),
+        });
+    }
+
+    let (size, block) = match siemens_size(&source.name) {
+        Some(size) => size,
+        None => (*sizes.siemens.get(&member.data_type.to_lowercase())?, true),
+    };
+
+    Some(match source.count {
+        None if size == 1 => (8, 8),
+        None if block => (align_up(size * 8, 16), 16),
+        None => (size * 8, 16),
+        Some(count) => {
+            let element = if block { align_up(size, 2) } else { size };
+            (align_up(count * element * 8, 16), 16)
+        }
+    })
+}
+
+/// Get the size and alignment of a member in Logix, where types are aligned to their size
+/// and BOOL arrays are stored in DINTs
+fn logix_member(member: &UdtMember, sizes: &Sizes) -> Option<Placement> {
+    let count = member.element_count().unwrap_or(1);
+    let element = |size, align| Placement { size, align };
+
+    let element = match member.data_type.to_uppercase().as_str() {
+        "BOOL" => {
+            return Some(Placement {
+                size: count.div_ceil(32) * 4,
+                align: 4,
+            })
+        }
+        "SINT" | "USINT" | "CHAR" => element(1, 1),
+        "INT" | "UINT" => element(2, 2),
+        "DINT" | "UDINT" | "REAL" => element(4, 4),
+        "LINT" | "ULINT" | "LREAL" | "LDT" => element(8, 8),
+        "STRING" => element(88, 4),
+        "TIMER" | "COUNTER" => element(12, 4),
+        name => *sizes.logix.get(&name.to_lowercase())?,
+    };
+
+    Some(Placement {
+        size: element.size * count,
+        align: element.align,
+    })
+}
+
+/// Create a hidden SINT array filling the given number of bytes
+fn padding(num: usize, bytes: usize, config: &Config) -> UdtMember {
+    UdtMember {
+        name: format!("{}Pad{}", config.naming.hidden_prefix, num),
+        description: None,
+        data_type: "SINT".to_string(),
+        array_bounds: Some((0, bytes as isize - 1)),
+        metadata: BTreeMap::new(),
+        hidden: true,
+        target: None,
+        bit_num: None,
+        span: None,
+        default: None,
+        radix: None,
+        access: None,
+        source: None,
+    }
+}
+
+/// Lay out a string type, which only exists in Logix
+fn lay_out_string(udt: &Udt, sizes: &mut Sizes) {
+    let mut offset = 0;
+    let mut align = 4;
+
+    for member in &udt.members {
+        let Some(placement) = logix_member(member, sizes) else {
+            return;
+        };
+        offset = align_up(offset, placement.align) + placement.size;
+        align = align.max(placement.align);
+    }
+
+    sizes.logix.insert(
+        udt.name.to_lowercase(),
+        Placement {
+            size: align_up(offset, align),
+            align,
+        },
+    );
+}
+
+/// Insert padding into the UDT so every member is at the byte offset it has in
+/// the non-optimized memory of an S7, warning about members that cannot be moved there
+fn lay_out(udt: &mut Udt, sizes: &mut Sizes, config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    let mut members = std::mem::take(&mut udt.members).into_iter();
+    let mut padded = vec![];
+    // Offset in bits in TIA Portal and in bytes in Logix
+    let mut siemens = 0;
+    let mut logix = 0;
+    let mut align = 4;
+    let mut pads = 0;
+
+    while let Some(member) = members.next() {
+        // BOOLs take the bits of the hidden SINT they are packed into, in both layouts
+        if member.target.is_some() {
+            siemens += 1;
+            padded.push(member);
+            continue;
+        }
+
+        let siemens_placement = if member.hidden {
+            Some((0, 8))
+        } else {
+            member
+                .source
+                .as_ref()
+                .and_then(|source| siemens_member(&member, source, sizes))
+        };
+
+        let (Some((bits, siemens_align)), Some(placement)) =
+            (siemens_placement, logix_member(&member, sizes))
+        else {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "layout-unknown",
+                    format!(
+                        "{}.{}: the size of {} is unknown, the members from here on are not padded",
+                        udt.name, member.name, member.data_type
+                    ),
+                )
+                .with_span(member.span)
+                .with_file(udt.file.clone())
+                .with_help("add the UDT to the input so its layout can be matched"),
+            );
+            padded.push(member);
+            padded.extend(members);
+            udt.members = padded;
+            return;
+        };
+
+        siemens = align_up(siemens, siemens_align);
+        let offset = siemens / 8;
+
+        // Padding only helps if Logix aligns the member to the offset it is moved to
+        if logix < offset && align_up(offset, placement.align) == offset {
+            padded.push(padding(pads, offset - logix, config));
+            pads += 1;
+            logix = offset;
+        }
+
+        let start = align_up(logix, placement.align);
+        if start != offset {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "layout-mismatch",
+                    format!(
+                        "{}.{}: starts at byte {} in Logix but at byte {} in TIA Portal",
+                        udt.name, member.name, start, offset
+                    ),
+                )
+                .with_span(member.span)
+                .with_file(udt.file.clone())
+                .with_help("Logix aligns members to their size, reorder the members or exchange them separately"),
+            );
+        }
+
+        logix = start + placement.size;
+        siemens += bits;
+        align = align.max(placement.align);
+        padded.push(member);
+    }
+
+    let siemens_size = align_up(siemens, 16) / 8;
+    if logix < siemens_size {
+        padded.push(padding(pads, siemens_size - logix, config));
+        logix = siemens_size;
+    }
+
+    if logix > siemens_size {
+        diagnostics.push(
+            Diagnostic::warning(
+                "layout-size",
+                format!(
+                    "{}: {} bytes in Logix but {} bytes in TIA Portal",
+                    udt.name, logix, siemens_size
+                ),
+            )
+            .with_span(udt.span)
+            .with_file(udt.file.clone()),
+        );
+    }
+
+    log::debug!(
+        "{}: laid out as {} bytes of TIA Portal with {} padding member(s)",
+        udt.name,
+        siemens_size,
+        pads
+    );
+
+    udt.members = padded;
+    sizes.siemens.insert(udt.name.to_lowercase(), siemens_size);
+    sizes.logix.insert(
+        udt.name.to_lowercase(),
+        Placement {
+            size: align_up(logix, align),
+            align,
+        },
+    );
+}
+
+/// Pad the UDTs to the byte layout of the same STRUCTs in the non-optimized memory
+/// of an S7, for exchanging them as raw data. UDTs are laid out after the UDTs they use
+pub fn pad_udts(udts: &mut [Udt], config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    let mut sizes = Sizes::default();
+    let mut done = vec![false; udts.len()];
+
+    loop {
+        // UDTs not in the list have an unknown size and don't hold up the others
+        let ready = |udt: &Udt| {
+            udt.members.iter().all(|member| {
+                udts.iter().zip(&done).all(|(other, done)| {
+                    *done || !other.name.eq_ignore_ascii_case(&member.data_type)
+                })
+            })
+        };
+        let Some(index) = (0..udts.len()).find(|&index| !done[index] && ready(&udts[index])) else {
+            break;
+        };
+
+        done[index] = true;
+        if udts[index].string {
+            lay_out_string(&udts[index], &mut sizes);
+        } else {
+            lay_out(&mut udts[index], &mut sizes, config, diagnostics);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Layout;
+    use crate::udt;
+
+    /// Convert a UDT source and pad it to the comms layout, returning its member names
+    /// and the codes of the diagnostics of the layout
+    fn pad(source: &str) -> (Vec<String>, Vec<&'static str>) {
+        let config = Config {
+            layout: Layout::Comms,
+            ..Config::default()
+        };
+        let mut udts = udt::get_udts(source, &config, &mut vec![]).unwrap();
+        let mut diagnostics = vec![];
+        pad_udts(&mut udts, &config, &mut diagnostics);
+
+        let names = udts
+            .last()
+            .unwrap()
+            .members
+            .iter()
+            .map(|member| member.name.clone())
+            .collect();
+        (
+            names,
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.code)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn pads_members_to_their_siemens_offset() {
+        let (names, codes) = pad(
+            "TYPE \"T\"\nSTRUCT\n   a : Byte;\n   b : Array[0..1] of Byte;\n   c : Int;\nEND_STRUCT;\nEND_TYPE",
+        );
+
+        assert_eq!(names, ["a", "ZZZZZZZZZZPad0", "b", "c"]);
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn reports_every_misplaced_member_without_useless_padding() {
+        let (names, codes) = pad(
+            "TYPE \"T\"\nSTRUCT\n   d : Bool;\n   e : Real;\n   f : Array[0..9] of Bool;\n   g : LReal;\nEND_STRUCT;\nEND_TYPE",
+        );
+
+        assert!(names.iter().all(|name| !name.contains("Pad")));
+        assert_eq!(
+            codes
+                .iter()
+                .filter(|&&code| code == "layout-mismatch")
+                .count(),
+            3
+        );
+        assert!(codes.contains(&"layout-size"));
+    }
+}
@@ -0,0 +1,78 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+/// ANSI escape codes for the colors used in messages
+pub const RED: &str = "\x1b[1;31m";
+pub const YELLOW: &str = "\x1b[1;33m";
+pub const BLUE: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+/// Check if messages should be colored, which is only the case when stderr
+/// is a terminal and the NO_COLOR convention is not used
+fn use_color() -> bool {
+    static USE_COLOR: OnceLock<bool> = OnceLock::new();
+    *USE_COLOR.get_or_init(|| io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none())
+}
+
+/// Color text with one of the color codes, if colors are enabled
+pub fn paint(text: &str, color: &str) -> String {
+    if use_color() {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Logger printing messages from this crate to stderr, prefixed with their severity
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+            && metadata
+                .target()
+                .starts_with(module_path!().split("::").next().unwrap_or_default())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match record.level() {
+            Level::Error => eprintln!("{} {}", paint("error:", RED), record.args()),
+            Level::Warn => eprintln!("{} {}", paint("warning:", YELLOW), record.args()),
+            Level::Info => eprintln!("{}", record.args()),
+            Level::Debug | Level::Trace => {
+                eprintln!(
+                    "{}: {}",
+                    record.level().as_str().to_lowercase(),
+                    record.args()
+                )
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Get the level to log at from the number of `-v` flags, or only errors when quiet
+fn level(verbose: u8, quiet: bool) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+/// Install the logger, only the first call has any effect
+pub fn init(verbose: u8, quiet: bool) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level(verbose, quiet));
+    }
+}
@@ -1,73 +1,1164 @@
-use std::env::args;
+use chrono::Local;
+use clap::Parser;
+use indicatif::{ProgressBar, ProgressStyle};
 use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::{Duration, Instant};
 
+mod aoi;
+mod cli;
+mod clipboard;
+mod config;
+mod data;
+mod diagnostic;
+mod encoding;
+mod error;
 mod l5x;
+mod layout;
+mod logger;
+mod manifest;
+mod parser;
+mod rename;
+mod schema;
+mod select;
+mod simatic_ml;
 mod udt;
+mod verify;
+mod watch;
+
+use cli::{Cli, Command, ConvertArgs, InputArgs, ManifestArgs};
+use config::{BoolPacking, Config, InterfaceLayout, Layout};
+use diagnostic::{print_diagnostics, print_error, Diagnostic, Severity};
+use error::{Error, EXIT_SUCCESS, EXIT_USAGE};
 
 fn main() {
-    let mut env_args = args().skip(1);
-    let mut input_path: Option<String> = None;
-    let mut output_path: Option<String> = None;
-
-    let help = "This is a tool for converting UDT files exported from TIA Portal\n\
-                        to an L5X XML format to import into Studio 5000\n\
-                        The following are valid options:\n\
-                        -i | --input  : Specify a UDT file to use as input\n\
-                        -o | --output : Specify the location and name to save the L5X\n\
-                        -h | --help   : Show this help dialogue";
-
-    if args().count() < 2 {
-        println!("{}", help);
-        exit(0);
-    }
-
-    while let Some(arg) = env_args.next() {
-        match arg.as_str() {
-            "-i" | "--input" => {
-                input_path = Some(env_args.next().expect("No argument given for input path!"))
-            }
-            "-o" | "--output" => {
-                output_path = Some(env_args.next().expect("No argument given for output path!"))
-            }
-            "-h" | "--help" => {
-                println!("{}", help)
-            }
-            _ => {
-                println!("Unknown command");
-                exit(0);
+    let cli = Cli::try_parse().unwrap_or_else(|err| {
+        // Print help and version normally, but keep exit codes of usage errors
+        // from colliding with the exit codes of conversion errors
+        let _ = err.print();
+        exit(if err.use_stderr() {
+            EXIT_USAGE
+        } else {
+            EXIT_SUCCESS
+        });
+    });
+    diagnostic::set_message_format(cli.message_format);
+    // Only JSON is printed on stderr so it can be consumed by other tools
+    logger::init(cli.verbose, cli.quiet || diagnostic::json_messages());
+
+    if let Err(err) = run(cli) {
+        print_error(None, &err);
+        exit(err.exit_code());
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Error> {
+    let mut config = config::load_config(cli.config.as_deref())?;
+
+    match cli.into_command() {
+        Command::Convert(args) => {
+            let args = resolve_args(args, &mut config)?;
+
+            if args.watch {
+                watch::watch(&args, &config)
+            } else {
+                convert(&args, &config).map(|_| ())
             }
         }
+        Command::List(args) => list(args, &config),
+        Command::Validate(args) => validate(args, &config),
+        Command::Batch(args) => batch(args, &config),
+    }
+}
+
+/// Fill in arguments missing from the command line with values from the config file,
+/// and override the config file with conversion settings given on the command line
+fn resolve_args(mut args: ConvertArgs, config: &mut Config) -> Result<ConvertArgs, Error> {
+    if args.from_clipboard {
+        args.input = Some(PathBuf::from(clipboard::CLIPBOARD_PATH));
+    }
+
+    if args.to_clipboard {
+        args.output = Some(PathBuf::from(clipboard::CLIPBOARD_PATH));
+    }
+
+    args.input = args.input.or_else(|| config.input.clone());
+    args.output = args.output.or_else(|| config.output.clone());
+    if args.output.is_none() {
+        args.output_dir = args.output_dir.or_else(|| config.output_dir.clone());
+    }
+
+    if !args.only.is_empty() {
+        config.only = args.only.clone();
+    }
+
+    if !args.exclude.is_empty() {
+        config.exclude = args.exclude.clone();
+    }
+
+    // Applied first, so the other options override it
+    if let Some(preset) = args.preset {
+        preset.apply(config);
+    }
+
+    if let Some(revision) = &args.software_revision {
+        config.software_revision = config::software_revision(revision).map_err(Error::Usage)?;
+    }
+
+    if let Some(revision) = &args.schema_revision {
+        config.schema_revision = config::schema_revision(revision).map_err(Error::Usage)?;
+    }
+
+    if let Some(name) = &args.controller_name {
+        config.controller_name = name.clone();
+    }
+
+    if rename::logix_name(&config.controller_name) != config.controller_name {
+        return Err(Error::Usage(format!(
+            "{} is not a legal Logix controller name",
+            config.controller_name
+        )));
+    }
+
+    if let Some(name) = &args.target_name {
+        config.target_name = Some(name.clone());
+    }
+
+    if let Some(interface) = args.interface {
+        config.interface = interface;
+    }
+
+    if let Some(wide_strings) = args.wide_strings {
+        config.wide_strings = wide_strings;
+    }
+
+    if let Some(default_string) = args.default_string {
+        config.default_string = default_string;
+    }
+
+    if let Some(length) = args.default_string_length {
+        config.default_string_length = length;
+    }
+
+    if let Some(chars) = args.chars {
+        config.chars = chars;
+    }
+
+    if let Some(bool_packing) = args.bool_packing {
+        config.bool_packing = bool_packing;
+    }
+
+    if args.no_bool_packing {
+        config.bool_packing = BoolPacking::None;
+    }
+
+    if let Some(bit_strings) = args.bit_strings {
+        config.bit_strings = bit_strings;
+    }
+
+    if let Some(system_types) = args.system_types {
+        config.system_types = system_types;
+    }
+
+    if let Some(unknown_types) = args.unknown_types {
+        config.unknown_types = unknown_types;
+    }
+
+    if let Some(layout) = args.layout {
+        config.layout = layout;
+    }
+
+    // The comms layout needs the BOOLs where they are in the S7 memory
+    let packing = args
+        .bool_packing
+        .filter(|&packing| packing != BoolPacking::Contiguous);
+    if config.layout == Layout::Comms && (packing.is_some() || args.no_bool_packing) {
+        return Err(Error::Usage(
+            "`--layout comms` packs BOOLs contiguously, it cannot be combined with `--bool-packing` or `--no-bool-packing`"
+                .to_string(),
+        ));
+    }
+
+    if let Some(version) = args.target_logix {
+        config.target_logix = Some(version);
+    }
+
+    if let Some(access) = args.default_external_access {
+        config.default_external_access = access;
+    }
+
+    if let Some(dtl) = args.dtl {
+        config.dtl = dtl;
+    }
+
+    if let Some(lower_bounds) = args.lower_bounds {
+        config.lower_bounds = lower_bounds;
+    }
+
+    if let Some(bool_arrays) = args.bool_arrays {
+        config.bool_arrays = bool_arrays;
+    }
+
+    if let Some(language) = &args.comment_language {
+        config.comment_language = Some(language.clone());
+    }
+
+    if let Some(placeholder) = &args.placeholder_type {
+        config.placeholder_type = Some(placeholder.clone());
+    }
+
+    if args.strict {
+        config.strict = true;
+    }
+
+    config.allow.extend(args.allow.iter().cloned());
+
+    if args.require_version {
+        config.require_version = true;
+    }
+
+    if args.version_in_description {
+        config.version_in_description = true;
+    }
+
+    if args.original_types {
+        config.original_types = true;
+    }
+
+    if args.lreal_as_real {
+        config.lreal_as_real = true;
+    }
+
+    if args.all_targets {
+        config.all_targets = true;
+    }
+
+    if let Some(date) = &args.export_date {
+        config.export_date = Some(date.clone());
+        config.no_export_date = false;
+    }
+
+    if args.no_export_date {
+        config.no_export_date = true;
+    }
+
+    if let Some(schema) = &args.schema {
+        config.schema = Some(schema.clone());
+    }
+
+    if let Some(indent_char) = args.indent_char {
+        config.indent_char = indent_char;
+        config.compact = false;
+    }
+
+    if let Some(indent_width) = args.indent_width {
+        config.indent_width = indent_width;
+        config.compact = false;
+    }
+
+    if args.compact {
+        config.compact = true;
+    }
+
+    if args.crlf {
+        config.crlf = true;
+    }
+
+    if args.bom {
+        config.bom = true;
+    }
+
+    if args.db_tags {
+        config.db_tags = true;
+    }
+
+    if args.emit_tags {
+        config.emit_tags = true;
+    }
+
+    if let Some(name) = &args.tag_name {
+        config.tag_name = Some(name.clone());
+    }
+
+    if let Some(program) = &args.program {
+        config.program = Some(program.clone());
+    }
+
+    for (kind, name) in [("tag", &config.tag_name), ("program", &config.program)] {
+        if let Some(name) = name
+            .as_ref()
+            .filter(|name| rename::logix_name(name) != **name)
+        {
+            return Err(Error::Usage(format!(
+                "{} is not a legal Logix {} name",
+                name, kind
+            )));
+        }
     }
 
-    if input_path == None {
-        println!("No input path given!");
-        exit(0);
+    if !args.library.is_empty() {
+        config.library = args.library.clone();
     }
 
-    if output_path == None {
-        println!("No output path given!");
-        exit(0);
+    if args.no_auto_include {
+        config.auto_include = false;
     }
 
-    let input = if let Some(path) = input_path {
-        fs::read_to_string(path).expect("Invalid input path!")
+    if let Some(prefix) = &args.type_prefix {
+        config.naming.type_prefix = prefix.clone();
+    }
+
+    if let Some(suffix) = &args.type_suffix {
+        config.naming.type_suffix = suffix.clone();
+    }
+
+    // Type mappings replace the ones of the config, ignoring case like the lookup
+    for (siemens, rockwell) in &args.map {
+        config
+            .types
+            .retain(|name, _| !name.eq_ignore_ascii_case(siemens));
+        config.types.insert(siemens.clone(), rockwell.clone());
+    }
+
+    if let Some(path) = args.rename_map.as_ref().or(config.rename_map.as_ref()) {
+        let renames = rename::load_rename_map(path)?;
+        config.rename.extend(renames);
+    }
+
+    if args.input.is_none() {
+        return Err(Error::Usage("No input path given!".to_string()));
+    }
+
+    Ok(args)
+}
+
+/// Path used on the command line to refer to stdin or stdout
+const STDIO_PATH: &str = "-";
+
+/// Read the input file, or stdin if the path is `-`, or the clipboard.
+/// Files and stdin are decoded from the encoding they were exported with,
+/// and every input gets LF line endings
+fn read_input(path: &Path) -> Result<String, Error> {
+    let input = if path == Path::new(clipboard::CLIPBOARD_PATH) {
+        clipboard::read()?
+    } else if path == Path::new(STDIO_PATH) {
+        let mut input = vec![];
+        io::stdin()
+            .read_to_end(&mut input)
+            .map_err(|err| Error::Io(format!("Failed to read stdin: {}", err)))?;
+        encoding::decode(&input)
     } else {
-        panic!("No input file specified!");
+        fs::read(path)
+            .map(|input| encoding::decode(&input))
+            .map_err(|err| Error::Io(format!("Failed to read {}: {}", path.display(), err)))?
     };
 
-    let mut udts = udt::get_udts(input);
+    Ok(encoding::normalize_line_endings(input))
+}
+
+/// Copy an existing file next to itself with a timestamp appended to its name
+fn backup_file(path: &Path) -> Result<(), Error> {
+    let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(Local::now().format(".%Y%m%d-%H%M%S.bak").to_string());
+    let backup = path.with_file_name(backup_name);
+
+    fs::copy(path, &backup)
+        .map(|_| ())
+        .map_err(|err| Error::Io(format!("Failed to back up {}: {}", path.display(), err)))
+}
+
+/// Write the output file, or stdout if the path is `-`, or the clipboard.
+/// Existing files are only replaced when forced or backed up first
+fn write_output(path: &Path, content: &[u8], args: &ConvertArgs) -> Result<(), Error> {
+    if path == Path::new(clipboard::CLIPBOARD_PATH) {
+        return clipboard::write(&String::from_utf8_lossy(content));
+    }
+
+    if path == Path::new(STDIO_PATH) {
+        return io::stdout()
+            .write_all(content)
+            .map_err(|err| Error::Io(format!("Failed to write stdout: {}", err)));
+    }
+
+    if path.exists() {
+        if args.backup {
+            backup_file(path)?;
+        } else if !args.force {
+            return Err(Error::Io(format!(
+                "{} already exists, use --force to overwrite it",
+                path.display()
+            )));
+        }
+    }
+
+    fs::write(path, content)
+        .map_err(|err| Error::Io(format!("Failed to write {}: {}", path.display(), err)))?;
+
+    log::debug!("Wrote {}", path.display());
+    Ok(())
+}
+
+/// Parse all UDTs from the content of the input file
+fn parse_udts(
+    path: &Path,
+    source: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<udt::Udt>, Error> {
+    let mut udts = udt::get_udts(source, config, diagnostics)?;
+
+    if udts.is_empty() {
+        return Err(Error::Parse(format!("No UDTs found in {}", path.display())));
+    }
+
+    for udt in &mut udts {
+        udt.file = Some(path.to_path_buf());
+    }
+
+    Ok(udts)
+}
+
+/// Remove the UDTs filtered out by the config, rename and decorate the rest
+/// and check their data types
+fn prepare_udts(
+    udts: Vec<udt::Udt>,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<udt::Udt>, Error> {
+    let (mut udts, mut excluded) = udt::filter_udts(udts, config)?;
+
+    if udts.is_empty() {
+        return Err(Error::Usage("All UDTs were filtered out".to_string()));
+    }
+
+    if config.require_version {
+        udt::check_versions(&udts, diagnostics)?;
+    }
+
+    rename::rename_udts(&mut udts, &config.rename);
+    for name in &mut excluded {
+        *name = rename::udt_name(&config.rename, name);
+    }
+    rename::decorate_udts(&mut udts, &mut excluded, &config.naming);
+    rename::sanitize_udts(&mut udts, &mut excluded, diagnostics);
+    rename::rename_reserved(&mut udts, &mut excluded, config, diagnostics);
+    let shortened = rename::shorten_names(&mut udts, &mut excluded, diagnostics);
+    if !shortened.is_empty() {
+        let table: Vec<String> = shortened
+            .iter()
+            .map(|(from, to)| format!("{},{}", from, to))
+            .collect();
+        log::info!("Shortened names:\n{}", table.join("\n"));
+    }
+    udt::canonical_references(&mut udts, &excluded, diagnostics);
+    let udts = udt::add_string_types(udts);
+
+    let mut udts = udt::check_types(udts, &excluded, config, diagnostics)?;
+    udt::check_revision_types(&udts, config, diagnostics);
+    udt::check_schema_revision(config, diagnostics);
+
+    if config.layout == Layout::Comms {
+        layout::pad_udts(&mut udts, config, diagnostics);
+    }
+    Ok(udts)
+}
+
+/// Generate the complete L5X document, using the last UDT as the parent.
+/// Returns the name of the parent UDT along with the document
+fn generate(
+    udts: Vec<udt::Udt>,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(String, Vec<u8>), Error> {
+    let mut udts = prepare_udts(udts, config, diagnostics)?;
+    let known = undefined_types(&udts);
+
+    // The sections of FB interfaces become Add-On Instructions, the rest their data types
+    if config.interface == InterfaceLayout::Aoi && udts.iter().any(|udt| udt.section.is_some()) {
+        let (sections, udts): (Vec<udt::Udt>, Vec<udt::Udt>) =
+            udts.into_iter().partition(|udt| udt.section.is_some());
+        let udts = udt::sort_udts(udts);
+        aoi::check_parameters(&sections, diagnostics);
+
+        let name = aoi::target_name(&sections).unwrap_or_default();
+        let l5x = aoi::create_l5x(&udts, &sections, config)
+            .map_err(|err| Error::Xml(format!("Failed to generate L5X: {}", err)))?;
+        return Ok((name, finish_document(l5x, &known, config)?));
+    }
+
+    if let Some(target) = &config.target_name {
+        match udts
+            .iter()
+            .position(|udt| udt.name.eq_ignore_ascii_case(target))
+        {
+            Some(index) => {
+                let udt = udts.remove(index);
+                udts.push(udt);
+            }
+            None => diagnostics.push(
+                Diagnostic::warning(
+                    "target-name",
+                    format!(
+                        "No UDT is named {}, it is only written as the TargetName",
+                        target
+                    ),
+                )
+                .with_help("Studio 5000 may reject an L5X whose target is not in it"),
+            ),
+        }
+    }
+
+    let parent_udt = udts
+        .pop()
+        .ok_or_else(|| Error::Parse("No UDTs to convert".to_string()))?;
+    log::debug!("Using {} as the target UDT", parent_udt.name);
+    let name = parent_udt.name.clone();
+
+    // Every UDT is a target, and the last one after sorting names the document
+    if config.all_targets {
+        udts.push(parent_udt);
+        let udts = udt::sort_udts(udts);
+        let name = udts.last().map_or(name, |udt| udt.name.clone());
+        return Ok((name, create_document(&[], &udts, &known, config)?));
+    }
 
-    let parent_udt = udts.pop().unwrap();
+    // Studio 5000 needs every UDT defined before the UDTs using it
+    let udts = udt::sort_udts(udts);
 
+    Ok((name, create_document(&udts, &[parent_udt], &known, config)?))
+}
+
+/// Get the types the UDTs reference without defining them, which must already exist in the
+/// project, such as undefined types passed through
+fn undefined_types(udts: &[udt::Udt]) -> Vec<String> {
+    let mut types: Vec<String> = vec![];
+
+    for name in udts.iter().flat_map(udt::referenced_types) {
+        let defined = udts
+            .iter()
+            .map(|udt| udt.name.as_str())
+            .chain(types.iter().map(String::as_str))
+            .any(|other| other.eq_ignore_ascii_case(name));
+        if !defined {
+            types.push(name.to_string());
+        }
+    }
+    types
+}
+
+/// Generate an L5X document with the targets and the other UDTs as context.
+/// The known types may be used without being defined by the document
+fn create_document(
+    udts: &[udt::Udt],
+    targets: &[udt::Udt],
+    known: &[String],
+    config: &Config,
+) -> Result<Vec<u8>, Error> {
+    let l5x = l5x::create_l5x(udts, targets, config)
+        .map_err(|err| Error::Xml(format!("Failed to generate L5X: {}", err)))?;
+    finish_document(l5x, known, config)
+}
+
+/// Get the generated document with the xml declaration at the beginning of the file,
+/// after checking it and validating it against the schema of the config if there is one
+fn finish_document(
+    writer: quick_xml::Writer<io::Cursor<Vec<u8>>>,
+    known: &[String],
+    config: &Config,
+) -> Result<Vec<u8>, Error> {
     // Not elegant, but it properly adds the xml declaration to the beginning of the file
-    let mut xml: Vec<u8> = "<?xml version=\"1.0\" ?>\n".into();
-    xml.append(
-        &mut l5x::create_l5x(&udts, parent_udt)
-            .unwrap()
-            .into_inner()
-            .into_inner(),
+    let mut xml: Vec<u8> = if config.bom {
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n".into()
+    } else {
+        "<?xml version=\"1.0\" ?>\n".into()
+    };
+    xml.append(&mut writer.into_inner().into_inner());
+
+    verify::verify(&xml, known)?;
+
+    if let Some(schema) = &config.schema {
+        schema::validate(&xml, schema)?;
+    }
+
+    if config.crlf {
+        xml = crlf_line_endings(&xml);
+    }
+
+    // The byte order mark is added last, the checks read the document without it
+    if config.bom {
+        xml.splice(0..0, *b"\xef\xbb\xbf");
+    }
+    Ok(xml)
+}
+
+/// Replace the line feeds of the document by CR LF, keeping line feeds that already follow a CR
+fn crlf_line_endings(xml: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(xml.len() + xml.len() / 32);
+
+    for (index, &byte) in xml.iter().enumerate() {
+        if byte == b'\n' && (index == 0 || xml[index - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+    }
+    converted
+}
+
+/// File extensions of UDT, DB and SCL sources and SimaticML documents exported from TIA Portal
+const SOURCE_EXTENSIONS: [&str; 4] = ["udt", "db", "scl", "xml"];
+
+/// Check if a file is a UDT, DB, SCL or SimaticML source by its extension
+fn is_source_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| {
+        SOURCE_EXTENSIONS
+            .iter()
+            .any(|source_ext| ext.eq_ignore_ascii_case(source_ext))
+    })
+}
+
+/// File extension of generated L5X files
+const L5X_EXTENSION: &str = "L5X";
+
+/// Recursively find all UDT, DB, SCL and SimaticML sources in a directory, sorted by path
+fn find_udt_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    let entries = fs::read_dir(dir)
+        .map_err(|err| Error::Io(format!("Failed to read {}: {}", dir.display(), err)))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| Error::Io(format!("Failed to read {}: {}", dir.display(), err)))?
+            .path();
+
+        if path.is_dir() {
+            files.append(&mut find_udt_files(&path)?);
+        } else if is_source_file(&path) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Drop the warnings whose code is allowed by the config, errors are always reported
+fn allow_diagnostics(diagnostics: &mut Vec<Diagnostic>, config: &Config) {
+    diagnostics.retain(|diagnostic| {
+        diagnostic.severity == Severity::Error
+            || !config
+                .allow
+                .iter()
+                .any(|code| code.eq_ignore_ascii_case(diagnostic.code))
+    });
+}
+
+/// Fail if any diagnostics were reported, used by `--check` to reject conversions with warnings
+fn check_diagnostics(diagnostics: &[Diagnostic]) -> Result<(), Error> {
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Check(format!(
+            "Conversion produced {} warning(s)",
+            diagnostics.len()
+        )))
+    }
+}
+
+/// Let the user pick the UDTs to convert if requested, otherwise convert all of them
+fn select_udts(udts: Vec<udt::Udt>, interactive: bool) -> Result<Vec<udt::Udt>, Error> {
+    if interactive {
+        select::select_udts(udts)
+    } else {
+        Ok(udts)
+    }
+}
+
+/// Write one L5X per UDT into the directory, named after the UDT,
+/// with the UDTs it references directly or through other UDTs as context
+fn write_split(
+    udts: Vec<udt::Udt>,
+    dir: &Path,
+    args: &ConvertArgs,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), Error> {
+    let udts = prepare_udts(udts, config, diagnostics)?;
+    let known = undefined_types(&udts);
+
+    if !args.check {
+        fs::create_dir_all(dir)
+            .map_err(|err| Error::Io(format!("Failed to create {}: {}", dir.display(), err)))?;
+    }
+
+    // String types are only written as the context of the UDTs using them
+    for udt in udts.iter().filter(|udt| !udt.string) {
+        let dependencies: Vec<udt::Udt> = udt::all_dependencies(udt, &udts)
+            .into_iter()
+            .cloned()
+            .collect();
+        let xml = create_document(&dependencies, std::slice::from_ref(udt), &known, config)?;
+
+        if !args.check {
+            let mut file_name = udt.name.clone();
+            file_name.push('.');
+            file_name.push_str(L5X_EXTENSION);
+            write_output(&dir.join(file_name), &xml, args)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the directory for output files named after their UDT when no output path is given:
+/// the output directory if given, otherwise the directory of the input
+fn derived_dir(input: &Path, args: &ConvertArgs) -> PathBuf {
+    if let Some(dir) = &args.output_dir {
+        dir.clone()
+    } else if input.is_dir() {
+        input.to_path_buf()
+    } else if is_glob(input) {
+        glob_base(input)
+    } else {
+        input.parent().unwrap_or(Path::new("")).to_path_buf()
+    }
+}
+
+/// Generate the L5X for the UDTs and the library UDTs they reference and write it
+/// to the output, or split it into one file per UDT if requested. Without an output path,
+/// the L5X is named after the parent UDT. Nothing is written when only checking
+fn write_udts(
+    udts: Vec<udt::Udt>,
+    library: &[udt::Udt],
+    input: &Path,
+    output: Option<&Path>,
+    args: &ConvertArgs,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), Error> {
+    let count = udts.len();
+    let mut udts = udt::add_library(udts, library);
+
+    // The library UDTs are added in front of the UDTs using them
+    if !config.auto_include {
+        let added = udts.len() - count;
+        for udt in udts.drain(..added) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "not-included",
+                    format!(
+                        "UDT {} is referenced and defined in the library, but not included",
+                        udt.name
+                    ),
+                )
+                .with_file(udt.file)
+                .with_help("remove `--no-auto-include` to add it to the L5X"),
+            );
+        }
+    }
+
+    if let Some(dir) = &args.split_output {
+        return write_split(udts, dir, args, config, diagnostics);
+    }
+
+    let (name, xml) = generate(udts, config, diagnostics)?;
+
+    if args.check {
+        return Ok(());
+    }
+
+    match output {
+        Some(output) => write_output(output, &xml, args),
+        None => {
+            let dir = derived_dir(input, args);
+            fs::create_dir_all(&dir)
+                .map_err(|err| Error::Io(format!("Failed to create {}: {}", dir.display(), err)))?;
+            write_output(&dir.join(format!("{}.{}", name, L5X_EXTENSION)), &xml, args)
+        }
+    }
+}
+
+/// Convert a single UDT file to a single L5X file
+fn convert_file(
+    input: &Path,
+    output: Option<&Path>,
+    library: &[udt::Udt],
+    args: &ConvertArgs,
+    config: &Config,
+) -> Result<Stats, Error> {
+    let source = read_input(input)?;
+    let mut diagnostics = vec![];
+    let result = parse_udts(input, &source, config, &mut diagnostics)
+        .and_then(|udts| select_udts(udts, args.interactive))
+        .and_then(|udts| {
+            let count = udts.len();
+            write_udts(udts, library, input, output, args, config, &mut diagnostics).map(|_| count)
+        });
+    allow_diagnostics(&mut diagnostics, config);
+    print_diagnostics(input, Some(&source), &diagnostics);
+    let udts = result?;
+
+    if args.check {
+        check_diagnostics(&diagnostics)?;
+    }
+
+    Ok(Stats {
+        udts,
+        warnings: diagnostics.len(),
+    })
+}
+
+/// Check if a path given on the command line is a glob pattern rather than a plain path
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Get the leading part of a glob pattern that contains no wildcards
+fn glob_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|component| !is_glob(Path::new(component.as_os_str())))
+        .collect()
+}
+
+/// Find all files matching a glob pattern, sorted by path
+fn find_glob_files(pattern: &Path) -> Result<Vec<PathBuf>, Error> {
+    let paths = glob::glob(&pattern.to_string_lossy()).map_err(|err| {
+        Error::Usage(format!(
+            "Invalid glob pattern {}: {}",
+            pattern.display(),
+            err
+        ))
+    })?;
+
+    let mut files = paths
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+
+    files.sort();
+    Ok(files)
+}
+
+/// Check if the output path refers to a directory, either existing or marked by a trailing separator
+fn is_output_dir(output: &Path) -> bool {
+    output.is_dir() || output.to_string_lossy().ends_with(std::path::is_separator)
+}
+
+/// Number of UDTs and warnings produced while converting a file
+#[derive(Default, Clone, Copy)]
+struct Stats {
+    udts: usize,
+    warnings: usize,
+}
+
+/// Result of converting one file of a batch
+struct FileReport {
+    file: PathBuf,
+    result: Result<Stats, Error>,
+    elapsed: Duration,
+}
+
+/// Run a conversion step for every file of a batch while showing a progress bar,
+/// recording the result and duration of every step
+fn run_batch(
+    files: &[PathBuf],
+    mut step: impl FnMut(&Path) -> Result<Stats, Error>,
+) -> Vec<FileReport> {
+    let progress = if log::log_enabled!(log::Level::Info) {
+        ProgressBar::new(files.len() as u64)
+    } else {
+        ProgressBar::hidden()
+    };
+    progress.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} [{elapsed}] {wide_msg}")
+            .expect("Invalid progress bar template!"),
+    );
+
+    let mut reports = vec![];
+
+    for file in files {
+        progress.set_message(file.display().to_string());
+        let start = Instant::now();
+
+        // Log messages would be overwritten by the progress bar while it is visible
+        let result = progress.suspend(|| step(file));
+
+        reports.push(FileReport {
+            file: file.clone(),
+            result,
+            elapsed: start.elapsed(),
+        });
+        progress.inc(1);
+    }
+
+    progress.finish_and_clear();
+    reports
+}
+
+/// Print the result of every file in a batch and a summary, and return an error if any failed.
+/// Returns the totals of the batch otherwise
+fn report(reports: &[FileReport]) -> Result<Stats, Error> {
+    let mut failed = 0;
+    let mut total = Stats::default();
+    let mut elapsed = Duration::ZERO;
+
+    for report in reports {
+        elapsed += report.elapsed;
+
+        match &report.result {
+            Ok(stats) => {
+                log::info!(
+                    "ok: {} ({} UDT(s), {} warning(s), {:.2?})",
+                    report.file.display(),
+                    stats.udts,
+                    stats.warnings,
+                    report.elapsed
+                );
+                total.udts += stats.udts;
+                total.warnings += stats.warnings;
+            }
+            Err(err) => {
+                print_error(Some(&report.file), err);
+                failed += 1;
+            }
+        }
+    }
+
+    log::info!(
+        "{} file(s): {} converted, {} failed, {} UDT(s) emitted, {} warning(s) in {:.2?}",
+        reports.len(),
+        reports.len() - failed,
+        failed,
+        total.udts,
+        total.warnings,
+        elapsed
     );
 
-    fs::write(output_path.unwrap(), xml).unwrap();
+    if failed > 0 {
+        Err(Error::Batch(format!(
+            "{} of {} file(s) failed to convert",
+            failed,
+            reports.len()
+        )))
+    } else {
+        Ok(total)
+    }
+}
+
+/// Convert every file to its own L5X file in the output directory,
+/// preserving the directory structure relative to the base.
+/// Without an output path, each L5X is named after its target UDT
+fn convert_to_dir(
+    files: &[PathBuf],
+    base: &Path,
+    library: &[udt::Udt],
+    args: &ConvertArgs,
+    config: &Config,
+) -> Result<Stats, Error> {
+    let reports = run_batch(files, |file| {
+        let relative = file.strip_prefix(base).unwrap_or(file);
+        let target = args
+            .output
+            .as_ref()
+            .filter(|_| !args.check)
+            .map(|output| output.join(relative).with_extension(L5X_EXTENSION));
+
+        if let Some(parent) = target.as_deref().and_then(Path::parent) {
+            fs::create_dir_all(parent).map_err(|err| {
+                Error::Io(format!("Failed to create {}: {}", parent.display(), err))
+            })?;
+        }
+
+        // Keep the directory structure for files named after their UDT as well
+        let mut file_args = args.clone();
+        file_args.output_dir = args
+            .output_dir
+            .as_ref()
+            .map(|dir| dir.join(relative.parent().unwrap_or(Path::new(""))));
+
+        convert_file(file, target.as_deref(), library, &file_args, config)
+    });
+
+    report(&reports)
+}
+
+/// Convert the UDTs of every file into a single combined L5X file
+fn convert_to_file(
+    files: &[PathBuf],
+    library: &[udt::Udt],
+    args: &ConvertArgs,
+    config: &Config,
+) -> Result<Stats, Error> {
+    let mut udts = vec![];
+
+    let reports = run_batch(files, |file| {
+        let source = read_input(file)?;
+        let mut diagnostics = vec![];
+        let result = parse_udts(file, &source, config, &mut diagnostics);
+        allow_diagnostics(&mut diagnostics, config);
+        print_diagnostics(file, Some(&source), &diagnostics);
+
+        let mut file_udts = result?;
+        let stats = Stats {
+            udts: file_udts.len(),
+            warnings: diagnostics.len(),
+        };
+        udts.append(&mut file_udts);
+
+        if args.check {
+            check_diagnostics(&diagnostics)?;
+        }
+        Ok(stats)
+    });
+
+    let mut stats = report(&reports)?;
+
+    let mut diagnostics = vec![];
+    let result = select_udts(udts, args.interactive).and_then(|udts| {
+        let output = args.output.as_deref();
+        let input = args.input();
+        write_udts(udts, library, input, output, args, config, &mut diagnostics)
+    });
+    // The UDTs come from several files, so there is no single source to show
+    allow_diagnostics(&mut diagnostics, config);
+    print_diagnostics(args.input(), None, &diagnostics);
+    result?;
+
+    if args.check {
+        check_diagnostics(&diagnostics)?;
+    }
+
+    stats.warnings += diagnostics.len();
+    Ok(stats)
+}
+
+/// Find the UDT files of a path given on the command line: the file itself,
+/// every UDT file in a directory, or every file matching a glob pattern
+fn find_files(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    if path.is_dir() {
+        find_udt_files(path)
+    } else if is_glob(path) {
+        find_glob_files(path)
+    } else {
+        Ok(vec![path.to_path_buf()])
+    }
+}
+
+/// Parse the UDTs of every library path in the config
+fn load_library(config: &Config) -> Result<Vec<udt::Udt>, Error> {
+    let mut library = vec![];
+
+    for path in &config.library {
+        for file in find_files(path)? {
+            let source = read_input(&file)?;
+            let mut diagnostics = vec![];
+            let result = parse_udts(&file, &source, config, &mut diagnostics);
+            allow_diagnostics(&mut diagnostics, config);
+            print_diagnostics(&file, Some(&source), &diagnostics);
+            library.append(&mut result?);
+        }
+    }
+
+    if !library.is_empty() {
+        log::debug!("Loaded {} UDT(s) from the library", library.len());
+    }
+    Ok(library)
+}
+
+fn convert(args: &ConvertArgs, config: &Config) -> Result<Stats, Error> {
+    let input = args.input();
+    let library = load_library(config)?;
+
+    if input.is_dir() {
+        let files = find_udt_files(input)?;
+
+        if files.is_empty() {
+            return Err(Error::Io(format!(
+                "No UDT files found in {}",
+                input.display()
+            )));
+        }
+
+        // Split output combines all files first, so each UDT gets one file
+        if args.split_output.is_some() {
+            convert_to_file(&files, &library, args, config)
+        } else {
+            convert_to_dir(&files, input, &library, args, config)
+        }
+    } else if is_glob(input) {
+        let files = find_glob_files(input)?;
+
+        if files.is_empty() {
+            return Err(Error::Io(format!("No files match {}", input.display())));
+        }
+
+        if args.split_output.is_none() && args.output.as_deref().is_none_or(is_output_dir) {
+            convert_to_dir(&files, &glob_base(input), &library, args, config)
+        } else {
+            convert_to_file(&files, &library, args, config)
+        }
+    } else {
+        convert_file(input, args.output.as_deref(), &library, args, config)
+    }
+}
+
+/// Run every conversion job of a manifest and report the result of each
+fn batch(args: ManifestArgs, config: &Config) -> Result<(), Error> {
+    let jobs = manifest::load_manifest(&args.manifest)?;
+    let inputs: Vec<PathBuf> = jobs.iter().map(|job| job.input().to_path_buf()).collect();
+    let mut jobs = jobs.into_iter();
+
+    // Jobs run in the order of their inputs
+    let reports = run_batch(&inputs, |_| {
+        let job = jobs.next().expect("one job per input");
+        let mut config = config.clone();
+        let job = resolve_args(job, &mut config)?;
+        convert(&job, &config)
+    });
+
+    report(&reports).map(|_| ())
+}
+
+fn list(args: InputArgs, config: &Config) -> Result<(), Error> {
+    let source = read_input(&args.input)?;
+    let mut diagnostics = vec![];
+    // References are spelled like the UDTs they reference, as in the L5X
+    let udts = parse_udts(&args.input, &source, config, &mut diagnostics).map(|mut udts| {
+        udt::canonical_references(&mut udts, &[], &mut diagnostics);
+        udts
+    });
+    allow_diagnostics(&mut diagnostics, config);
+    print_diagnostics(&args.input, Some(&source), &diagnostics);
+
+    for udt in udts? {
+        let references = udt::referenced_types(&udt);
+        let members = udt.members.iter().filter(|member| !member.hidden).count();
+
+        println!("{}", udt.name);
+        println!("  version: {}", udt.version);
+        if let Some(description) = &udt.description {
+            println!("  description: {}", description);
+        }
+        println!("  members: {}", members);
+        if !references.is_empty() {
+            println!("  references: {}", references.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn validate(args: InputArgs, config: &Config) -> Result<(), Error> {
+    let source = read_input(&args.input)?;
+    let mut diagnostics = vec![];
+    let xml = parse_udts(&args.input, &source, config, &mut diagnostics)
+        .and_then(|udts| generate(udts, config, &mut diagnostics));
+    allow_diagnostics(&mut diagnostics, config);
+    print_diagnostics(&args.input, Some(&source), &diagnostics);
+    xml?;
+    check_diagnostics(&diagnostics)?;
+
+    println!("{}: OK", args.input.display());
+    Ok(())
 }
@@ -0,0 +1,193 @@
+use crate::cli::ConvertArgs;
+use crate::config::{
+    relative_to, BitStrings, BoolArrays, BoolPacking, Chars, DefaultString, Dtl, ExternalAccess,
+    IndentChar, InterfaceLayout, Layout, LowerBounds, Preset, SystemTypes, UnknownTypes,
+    WideStrings,
+};
+use crate::error::Error;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A single conversion listed in a manifest, with the same options as `convert`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Job {
+    input: PathBuf,
+    output: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    split_output: Option<PathBuf>,
+    #[serde(default)]
+    force: bool,
+    #[serde(default)]
+    backup: bool,
+    #[serde(default)]
+    check: bool,
+    #[serde(default)]
+    only: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    library: Vec<PathBuf>,
+    #[serde(default)]
+    no_auto_include: bool,
+    rename_map: Option<PathBuf>,
+    #[serde(default)]
+    map: BTreeMap<String, String>,
+    type_prefix: Option<String>,
+    type_suffix: Option<String>,
+    #[serde(default)]
+    db_tags: bool,
+    #[serde(default)]
+    emit_tags: bool,
+    tag_name: Option<String>,
+    program: Option<String>,
+    interface: Option<InterfaceLayout>,
+    #[serde(default)]
+    require_version: bool,
+    #[serde(default)]
+    version_in_description: bool,
+    #[serde(default)]
+    original_types: bool,
+    #[serde(default)]
+    lreal_as_real: bool,
+    export_date: Option<String>,
+    #[serde(default)]
+    no_export_date: bool,
+    schema: Option<PathBuf>,
+    indent_char: Option<IndentChar>,
+    indent_width: Option<usize>,
+    #[serde(default)]
+    compact: bool,
+    #[serde(default)]
+    crlf: bool,
+    #[serde(default)]
+    bom: bool,
+    wide_strings: Option<WideStrings>,
+    default_string: Option<DefaultString>,
+    default_string_length: Option<usize>,
+    chars: Option<Chars>,
+    preset: Option<Preset>,
+    software_revision: Option<String>,
+    schema_revision: Option<String>,
+    controller_name: Option<String>,
+    target_name: Option<String>,
+    #[serde(default)]
+    all_targets: bool,
+    bool_packing: Option<BoolPacking>,
+    #[serde(default)]
+    no_bool_packing: bool,
+    bit_strings: Option<BitStrings>,
+    system_types: Option<SystemTypes>,
+    dtl: Option<Dtl>,
+    lower_bounds: Option<LowerBounds>,
+    bool_arrays: Option<BoolArrays>,
+    unknown_types: Option<UnknownTypes>,
+    layout: Option<Layout>,
+    target_logix: Option<u32>,
+    default_external_access: Option<ExternalAccess>,
+    placeholder_type: Option<String>,
+    comment_language: Option<String>,
+    #[serde(default)]
+    strict: bool,
+    #[serde(default)]
+    allow: Vec<String>,
+}
+
+/// List of conversions run by the `batch` subcommand
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    job: Vec<Job>,
+}
+
+impl Job {
+    /// Turn the job into convert arguments, with paths relative to the manifest directory
+    fn into_args(self, base: &Path) -> ConvertArgs {
+        ConvertArgs {
+            input: Some(relative_to(base, self.input)),
+            output: self.output.map(|output| relative_to(base, output)),
+            output_dir: self.output_dir.map(|dir| base.join(dir)),
+            split_output: self.split_output.map(|dir| base.join(dir)),
+            force: self.force,
+            backup: self.backup,
+            check: self.check,
+            only: self.only,
+            exclude: self.exclude,
+            library: self.library.iter().map(|path| base.join(path)).collect(),
+            no_auto_include: self.no_auto_include,
+            rename_map: self.rename_map.map(|map| base.join(map)),
+            map: self.map.into_iter().collect(),
+            type_prefix: self.type_prefix,
+            type_suffix: self.type_suffix,
+            db_tags: self.db_tags,
+            emit_tags: self.emit_tags,
+            tag_name: self.tag_name,
+            program: self.program,
+            interface: self.interface,
+            require_version: self.require_version,
+            version_in_description: self.version_in_description,
+            original_types: self.original_types,
+            lreal_as_real: self.lreal_as_real,
+            export_date: self.export_date,
+            no_export_date: self.no_export_date,
+            schema: self.schema.map(|schema| base.join(schema)),
+            indent_char: self.indent_char,
+            indent_width: self.indent_width,
+            compact: self.compact,
+            crlf: self.crlf,
+            bom: self.bom,
+            wide_strings: self.wide_strings,
+            default_string: self.default_string,
+            default_string_length: self.default_string_length,
+            chars: self.chars,
+            preset: self.preset,
+            software_revision: self.software_revision,
+            schema_revision: self.schema_revision,
+            controller_name: self.controller_name,
+            target_name: self.target_name,
+            all_targets: self.all_targets,
+            bool_packing: self.bool_packing,
+            no_bool_packing: self.no_bool_packing,
+            bit_strings: self.bit_strings,
+            system_types: self.system_types,
+            dtl: self.dtl,
+            lower_bounds: self.lower_bounds,
+            bool_arrays: self.bool_arrays,
+            unknown_types: self.unknown_types,
+            layout: self.layout,
+            target_logix: self.target_logix,
+            default_external_access: self.default_external_access,
+            placeholder_type: self.placeholder_type,
+            comment_language: self.comment_language,
+            strict: self.strict,
+            allow: self.allow,
+            ..ConvertArgs::default()
+        }
+    }
+}
+
+/// Load the jobs of a manifest file as convert arguments
+pub fn load_manifest(path: &Path) -> Result<Vec<ConvertArgs>, Error> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| Error::Io(format!("Failed to read {}: {}", path.display(), err)))?;
+
+    let manifest: Manifest = toml::from_str(&content)
+        .map_err(|err| Error::Config(format!("Invalid manifest {}: {}", path.display(), err)))?;
+
+    if manifest.job.is_empty() {
+        return Err(Error::Config(format!(
+            "Manifest {} has no jobs",
+            path.display()
+        )));
+    }
+
+    let base = path.parent().unwrap_or(Path::new(""));
+    Ok(manifest
+        .job
+        .into_iter()
+        .map(|job| job.into_args(base))
+        .collect())
+}
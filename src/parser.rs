@@ -0,0 +1,1801 @@
+use crate::diagnostic::Span;
+use std::fmt;
+
+/// Kinds of tokens in UDT sources exported from TIA Portal
+#[derive(Clone, PartialEq, Debug)]
+enum TokenKind {
+    /// Identifier or keyword, such as `STRUCT` or `Bool`
+    Ident(String),
+    /// Name in double quotes, such as `"MotorData"`
+    Quoted(String),
+    /// Text in single quotes, such as `'TRUE'`, or the text of a TITLE
+    Text(String),
+    /// Number, such as `15` or `0.1`
+    Number(String),
+    /// Text of a `//` or `(* *)` comment
+    Comment(String),
+    /// `:=`
+    Assign,
+    /// `..`
+    Range,
+    /// Any other character
+    Symbol(char),
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Ident(text) | TokenKind::Number(text) => write!(f, "`{}`", text),
+            TokenKind::Quoted(text) => write!(f, "`\"{}\"`", text),
+            TokenKind::Text(text) => write!(f, "`'{}'`", text),
+            TokenKind::Comment(_) => write!(f, "comment"),
+            TokenKind::Assign => write!(f, "`:=`"),
+            TokenKind::Range => write!(f, "`..`"),
+            TokenKind::Symbol(symbol) => write!(f, "`{}`", symbol),
+        }
+    }
+}
+
+/// Dimensions of an array with their span
+type Dimensions = (Vec<Dimension>, Span);
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    span: Span,
+}
+
+/// A syntax error in the input
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
+    /// The input is valid, but uses a construct that cannot be converted
+    pub unsupported: bool,
+}
+
+/// Types declared in an input, along with the errors of the members and blocks
+/// skipped because they could not be parsed
+pub struct Parsed {
+    pub types: Vec<TypeDecl>,
+    pub errors: Vec<ParseError>,
+}
+
+/// An attribute such as `ExternalWritable := 'False'`
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub value: String,
+}
+
+/// An array bound, either a number or the name of a constant
+#[derive(Clone, Debug)]
+pub enum Bound {
+    Value(isize),
+    Constant(String),
+}
+
+/// Lower and upper bound of one dimension of an array
+pub type Dimension = (Bound, Bound);
+
+/// Data type of a member as written in the input
+#[derive(Clone, Debug)]
+pub enum DataType {
+    /// Named type such as `Bool`, `String[20]` or `"MotorData"`
+    Named(String),
+    /// Anonymous nested `STRUCT` with its members
+    Struct(Vec<MemberDecl>),
+}
+
+/// A member of a STRUCT as written in the input
+#[derive(Clone, Debug)]
+pub struct MemberDecl {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    /// Type of the member, or of its elements if it is an array
+    pub data_type: DataType,
+    pub type_span: Span,
+    /// Dimensions of the member if it is an array, empty otherwise
+    pub dimensions: Vec<Dimension>,
+    pub bounds_span: Option<Span>,
+    /// Initial value as written, with one value per element if it is an array
+    pub initial: Option<Vec<String>>,
+    /// Comment following the member on the same line
+    pub comment: Option<String>,
+}
+
+/// A start value assigned after `BEGIN` of a data block, such as `motors[2].speed := 5.0;`
+#[derive(Clone, Debug)]
+pub struct Assignment {
+    /// Members leading to the assigned one, each with the indices written after it
+    pub path: Vec<(String, Vec<isize>)>,
+    /// Value as written, like an initial value
+    pub value: String,
+    pub span: Span,
+}
+
+/// Kind of block a type is declared in
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockKind {
+    /// `TYPE`, a PLC data type
+    Type,
+    /// `DATA_BLOCK`, a global or instance DB
+    DataBlock,
+    /// `FUNCTION_BLOCK`, whose interface is split into sections
+    FunctionBlock,
+    /// `FUNCTION`, whose interface is split into sections
+    Function,
+}
+
+/// Kind of a section of a block interface
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SectionKind {
+    Input,
+    Output,
+    InOut,
+    Static,
+    Temp,
+    Constant,
+}
+
+/// A `VAR_*` section of a block interface with its members
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub kind: SectionKind,
+    pub members: Vec<MemberDecl>,
+}
+
+/// A block declaring a type as written in the input
+#[derive(Clone, Debug)]
+pub struct TypeDecl {
+    pub name: String,
+    pub name_span: Span,
+    pub block: BlockKind,
+    pub title: Option<String>,
+    pub version: Option<String>,
+    pub attributes: Vec<Attribute>,
+    /// Members of a PLC data type or global DB
+    pub members: Vec<MemberDecl>,
+    /// Interface sections of an FB or FC
+    pub sections: Vec<Section>,
+    /// Type of an instance DB, which has no members of its own
+    pub instance_of: Option<String>,
+    /// Start values assigned after `BEGIN` of a DB, replacing the initial values
+    pub assignments: Vec<Assignment>,
+}
+
+impl TypeDecl {
+    pub fn new(name: String, name_span: Span, block: BlockKind) -> TypeDecl {
+        TypeDecl {
+            name,
+            name_span,
+            block,
+            title: None,
+            version: None,
+            attributes: vec![],
+            members: vec![],
+            sections: vec![],
+            instance_of: None,
+            assignments: vec![],
+        }
+    }
+}
+
+/// Find the end of a TITLE starting at `start`, which is the end of its line unless the
+/// following lines continue it. A line continues the title unless it is empty or starts
+/// another header entry or the structure, such as `VERSION : 0.1`, `{`, `NON_RETAIN` or `STRUCT`
+fn title_end(source: &str, start: usize) -> usize {
+    let line_end = |pos: usize| {
+        source[pos..]
+            .find('\n')
+            .map_or(source.len(), |end| pos + end)
+    };
+    let mut end = line_end(start);
+
+    while end < source.len() {
+        let next = line_end(end + 1);
+        let line = source[end + 1..next].trim();
+        let (word, rest) = line.split_at(
+            line.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(line.len()),
+        );
+        let upper = word.to_uppercase();
+
+        let starts_entry = word.is_empty()
+            || rest.trim_start().starts_with(':')
+            || ["STRUCT", "BEGIN", "TITLE"].contains(&upper.as_str())
+            || upper.starts_with("VAR")
+            || upper.starts_with("END_")
+            // Flags such as NON_RETAIN
+            || (rest.trim().is_empty() && word == upper);
+        if starts_entry {
+            break;
+        }
+        end = next;
+    }
+
+    end
+}
+
+/// Split the input into tokens
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    // Position of the last token, to find the line and column of the next one without rescanning
+    let mut position = (0, 1, 0);
+    // The text of a TITLE follows, which may hold any characters and span lines
+    let mut title = false;
+
+    while let Some((start, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+
+        let kind = match c {
+            '\n' if title => {
+                title = false;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            c if title && c != '\'' => {
+                let end = title_end(source, start);
+                while chars.next_if(|&(pos, _)| pos < end).is_some() {}
+
+                let text = source[start..end]
+                    .lines()
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                TokenKind::Text(text)
+            }
+            '/' if next == Some('/') => {
+                let end = source[start..]
+                    .find('\n')
+                    .map_or(source.len(), |end| start + end);
+                while chars.next_if(|&(pos, _)| pos < end).is_some() {}
+                TokenKind::Comment(source[start + 2..end].trim().to_string())
+            }
+            '(' if next == Some('*') => {
+                let end = source[start + 2..]
+                    .find("*)")
+                    .map(|end| start + 2 + end)
+                    .ok_or_else(|| ParseError {
+                        message: "unterminated comment".to_string(),
+                        span: Span::new(source, start, source.len()),
+                        unsupported: false,
+                    })?;
+                while chars.next_if(|&(pos, _)| pos < end + 2).is_some() {}
+
+                // Comments spanning several lines keep their line breaks, without the indentation
+                let text = source[start + 2..end]
+                    .lines()
+                    .map(str::trim)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                TokenKind::Comment(text.trim().to_string())
+            }
+            '"' | '\'' => {
+                // `$` escapes the next character in SCL strings, such as `$'`
+                let end = source[start + 1..]
+                    .char_indices()
+                    .scan(false, |escaped, (pos, next)| {
+                        let end = !*escaped && next == c;
+                        *escaped = !*escaped && c == '\'' && next == '$';
+                        Some((pos, end))
+                    })
+                    .find(|&(_, end)| end)
+                    .map(|(end, _)| start + 1 + end)
+                    .ok_or_else(|| ParseError {
+                        message: "unterminated quotes".to_string(),
+                        span: Span::new(source, start, source.len()),
+                        unsupported: false,
+                    })?;
+                while chars.next_if(|&(pos, _)| pos <= end).is_some() {}
+
+                let text = source[start + 1..end].to_string();
+                if c == '"' {
+                    TokenKind::Quoted(text)
+                } else {
+                    TokenKind::Text(text)
+                }
+            }
+            ':' if next == Some('=') => {
+                chars.next();
+                TokenKind::Assign
+            }
+            '.' if next == Some('.') => {
+                chars.next();
+                TokenKind::Range
+            }
+            c if c.is_ascii_digit() => {
+                let mut end = start + 1;
+                while let Some((pos, _)) = chars.next_if(|&(_, c)| c.is_ascii_digit()) {
+                    end = pos + 1;
+                }
+
+                // A dot followed by a digit continues a decimal number, `..` starts a range
+                if source[end..].starts_with('.')
+                    && source[end + 1..].starts_with(|c: char| c.is_ascii_digit())
+                {
+                    chars.next();
+                    while let Some((pos, _)) = chars.next_if(|&(_, c)| c.is_ascii_digit()) {
+                        end = pos + 1;
+                    }
+                }
+                TokenKind::Number(source[start..end].to_string())
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((pos, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_')
+                {
+                    end = pos + c.len_utf8();
+                }
+                TokenKind::Ident(source[start..end].to_string())
+            }
+            c => TokenKind::Symbol(c),
+        };
+
+        let (offset, line, line_start) = position;
+        let line = line + source[offset..start].matches('\n').count();
+        let line_start = source[offset..start]
+            .rfind('\n')
+            .map_or(line_start, |pos| offset + pos + 1);
+        position = (start, line, line_start);
+
+        title = kind == TokenKind::Symbol('=')
+            && matches!(tokens.last(), Some(Token { kind: TokenKind::Ident(ident), .. }) if ident.eq_ignore_ascii_case("TITLE"));
+
+        let end = chars.peek().map_or(source.len(), |&(pos, _)| pos);
+        tokens.push(Token {
+            kind,
+            span: Span {
+                start,
+                end,
+                line,
+                column: source[line_start..start].chars().count() + 1,
+            },
+        });
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser over the tokens of the input
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Errors of the constructs skipped to continue parsing
+    errors: Vec<ParseError>,
+}
+
+impl Parser<'_> {
+    /// Index of the next token that is not a comment
+    fn next_index(&self) -> usize {
+        let mut index = self.pos;
+        while matches!(
+            self.tokens.get(index),
+            Some(Token {
+                kind: TokenKind::Comment(_),
+                ..
+            })
+        ) {
+            index += 1;
+        }
+        index
+    }
+
+    /// Look at the next token without consuming it, skipping comments
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next_index())
+    }
+
+    /// Consume the next token, skipping comments
+    fn next(&mut self) -> Option<Token> {
+        let index = self.next_index();
+        let token = self.tokens.get(index).cloned();
+        self.pos = index + usize::from(token.is_some());
+        token
+    }
+
+    /// Span used for errors at the end of the input
+    fn end_span(&self) -> Span {
+        Span::new(self.source, self.source.len(), self.source.len())
+    }
+
+    /// Error pointing at the next token
+    fn error(&self, expected: &str) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError {
+                message: format!("expected {}, found {}", expected, token.kind),
+                span: token.span,
+                unsupported: false,
+            },
+            None => ParseError {
+                message: format!("expected {}, found end of input", expected),
+                span: self.end_span(),
+                unsupported: false,
+            },
+        }
+    }
+
+    /// Check if the next token is the keyword, ignoring case
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Ident(ident), .. }) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Check if the next token is the symbol
+    fn at_symbol(&self, symbol: char) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Symbol(c), .. }) if *c == symbol)
+    }
+
+    /// Consume the keyword, ignoring case
+    fn expect_keyword(&mut self, keyword: &str) -> Result<Span, ParseError> {
+        if self.at_keyword(keyword) {
+            Ok(self.next().expect("keyword was peeked").span)
+        } else {
+            Err(self.error(&format!("`{}`", keyword)))
+        }
+    }
+
+    /// Consume the keyword if it is next, ignoring case
+    fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    /// Consume the token
+    fn expect(&mut self, kind: TokenKind) -> Result<Span, ParseError> {
+        match self.peek() {
+            Some(token) if token.kind == kind => Ok(self.next().expect("token was peeked").span),
+            _ => Err(self.error(&kind.to_string())),
+        }
+    }
+
+    /// Consume the token if it is next
+    fn accept(&mut self, kind: TokenKind) -> bool {
+        let found = self.peek().is_some_and(|token| token.kind == kind);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    /// Consume a name, either quoted or a plain identifier
+    fn expect_name(&mut self, expected: &str) -> Result<(String, Span), ParseError> {
+        match self.peek().cloned() {
+            Some(Token {
+                kind: TokenKind::Quoted(name) | TokenKind::Ident(name),
+                span,
+            }) => {
+                self.next();
+                Ok((name, span))
+            }
+            _ => Err(self.error(expected)),
+        }
+    }
+
+    /// Consume an integer
+    fn expect_integer(&mut self, expected: &str) -> Result<(isize, Span), ParseError> {
+        match self.peek().cloned() {
+            Some(Token {
+                kind: TokenKind::Number(number),
+                span,
+            }) => {
+                let value = number.parse().map_err(|_| ParseError {
+                    message: format!("{} must be an integer, found `{}`", expected, number),
+                    span,
+                    unsupported: false,
+                })?;
+                self.next();
+                Ok((value, span))
+            }
+            _ => Err(self.error(expected)),
+        }
+    }
+
+    /// Consume an integer with an optional sign, such as an array bound
+    fn expect_signed(&mut self, expected: &str) -> Result<(isize, Span), ParseError> {
+        let sign = match self.peek().map(|token| (&token.kind, token.span)) {
+            Some((TokenKind::Symbol(sign @ ('-' | '+')), span)) => Some((*sign, span)),
+            _ => None,
+        };
+
+        let Some((sign, sign_span)) = sign else {
+            return self.expect_integer(expected);
+        };
+
+        self.next();
+        let (value, span) = self.expect_integer(expected)?;
+        let value = if sign == '-' { -value } else { value };
+        Ok((value, sign_span.to(span)))
+    }
+
+    /// Consume an array bound, either an integer or the name of a constant
+    fn expect_bound(&mut self, expected: &str) -> Result<(Bound, Span), ParseError> {
+        match self.peek().cloned() {
+            Some(Token {
+                kind: TokenKind::Quoted(name) | TokenKind::Ident(name),
+                span,
+            }) => {
+                self.next();
+                Ok((Bound::Constant(name), span))
+            }
+            _ => {
+                let (value, span) = self.expect_signed(expected)?;
+                Ok((Bound::Value(value), span))
+            }
+        }
+    }
+
+    /// Parse the dimensions of an array such as `[0..9, -1..1]`, without the brackets
+    fn parse_dimensions(&mut self) -> Result<Dimensions, ParseError> {
+        let mut dimensions = vec![];
+        let start = self.peek().map_or(self.end_span(), |token| token.span);
+
+        let span = loop {
+            // Dynamic arrays of FB and FC interfaces, such as `Array[*] of Int`
+            if let Some(Token {
+                kind: TokenKind::Symbol('*'),
+                span,
+            }) = self.peek()
+            {
+                return Err(ParseError {
+                    message: "`Array[*]` has a variable length, which Logix has no equivalent for, specify fixed bounds such as `Array[0..9]`".to_string(),
+                    span: *span,
+                    unsupported: true,
+                });
+            }
+
+            let (lower, lower_span) = self.expect_bound("lower bound")?;
+            self.expect(TokenKind::Range)?;
+            let (upper, upper_span) = self.expect_bound("upper bound")?;
+
+            if let (Bound::Value(lower), Bound::Value(upper)) = (&lower, &upper) {
+                if upper < lower {
+                    return Err(ParseError {
+                        message: format!(
+                            "upper bound {} is less than lower bound {}",
+                            upper, lower
+                        ),
+                        span: lower_span.to(upper_span),
+                        unsupported: false,
+                    });
+                }
+            }
+
+            dimensions.push((lower, upper));
+
+            if !self.accept(TokenKind::Symbol(',')) {
+                break start.to(upper_span);
+            }
+        };
+
+        Ok((dimensions, span))
+    }
+
+    /// Consume the rest of the current line and return its text
+    fn rest_of_line(&mut self, after: usize) -> String {
+        let end = self.source[after..]
+            .find('\n')
+            .map_or(self.source.len(), |end| after + end);
+
+        while self.pos < self.tokens.len() && self.tokens[self.pos].span.start < end {
+            self.pos += 1;
+        }
+
+        self.source[after..end].trim().to_string()
+    }
+
+    /// Consume a comment following the given offset on the same line, if any
+    fn trailing_comment(&mut self, after: usize) -> Option<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token {
+                kind: TokenKind::Comment(comment),
+                span,
+            }) if !self.source[after..span.start].contains('\n') => {
+                let comment = comment.clone();
+                self.pos += 1;
+                Some(comment)
+            }
+            _ => None,
+        }
+    }
+
+    /// Consume the comments on the lines before a member, joined by line breaks.
+    /// A comment on the line of the previous token belongs to that token, such as the
+    /// comment after the `Struct` of a nested STRUCT, and is skipped
+    fn leading_comments(&mut self) -> Option<String> {
+        let mut comments = vec![];
+
+        while let Some(Token {
+            kind: TokenKind::Comment(comment),
+            span,
+        }) = self.tokens.get(self.pos)
+        {
+            let own_line = match self.pos.checked_sub(1).map(|index| &self.tokens[index]) {
+                Some(previous) => self.source[previous.span.end..span.start].contains('\n'),
+                None => true,
+            };
+            if own_line && !comment.is_empty() {
+                comments.push(comment.clone());
+            }
+            self.pos += 1;
+        }
+
+        (!comments.is_empty()).then(|| comments.join("\n"))
+    }
+
+    /// Get the comments written inside a member declaration, between its data type
+    /// and its `;`, or on the line of the `Struct` of a nested STRUCT
+    fn inner_comments(&self, data_type: &DataType, type_span: Span, end: usize) -> Vec<String> {
+        let index = self
+            .tokens
+            .partition_point(|token| token.span.start < type_span.start);
+        let tokens = self.tokens[index..].iter().skip(1);
+
+        let comments = match data_type {
+            DataType::Struct(_) => tokens
+                .take(1)
+                .filter(|token| !self.source[type_span.end..token.span.start].contains('\n'))
+                .collect::<Vec<_>>(),
+            DataType::Named(_) => tokens.take_while(|token| token.span.end < end).collect(),
+        };
+
+        comments
+            .into_iter()
+            .filter_map(|token| match &token.kind {
+                TokenKind::Comment(comment) if !comment.is_empty() => Some(comment.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Parse an attribute block such as `{ ExternalWritable := 'False' }`
+    fn parse_attributes(&mut self) -> Result<Vec<Attribute>, ParseError> {
+        let mut attributes = vec![];
+        self.expect(TokenKind::Symbol('{'))?;
+
+        while !self.accept(TokenKind::Symbol('}')) {
+            let (name, _) = self.expect_name("attribute name")?;
+            self.expect(TokenKind::Assign)?;
+
+            let value = match self.peek().map(|token| &token.kind) {
+                Some(
+                    TokenKind::Text(value) | TokenKind::Ident(value) | TokenKind::Number(value),
+                ) => value.clone(),
+                _ => return Err(self.error("attribute value")),
+            };
+            self.next();
+
+            attributes.push(Attribute { name, value });
+
+            if !self.accept(TokenKind::Symbol(';')) && !self.at_symbol('}') {
+                return Err(self.error("`;` or `}`"));
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Parse a data type, returning it with its span and array bounds
+    fn parse_data_type(&mut self) -> Result<(DataType, Span, Option<Dimensions>), ParseError> {
+        if self.at_keyword("ARRAY") {
+            let start = self.next().expect("keyword was peeked").span;
+            self.expect(TokenKind::Symbol('['))?;
+            let dimensions = self.parse_dimensions()?;
+            self.expect(TokenKind::Symbol(']'))?;
+            self.expect_keyword("OF")?;
+
+            if self.at_keyword("ARRAY") {
+                return Err(ParseError {
+                    message: "arrays of arrays are not supported".to_string(),
+                    span: start,
+                    unsupported: true,
+                });
+            }
+
+            let (data_type, span, _) = self.parse_data_type()?;
+            return Ok((data_type, span, Some(dimensions)));
+        }
+
+        if self.at_keyword("STRUCT") {
+            let span = self.next().expect("keyword was peeked").span;
+            let members = self.parse_members("END_STRUCT")?;
+            return Ok((DataType::Struct(members), span, None));
+        }
+
+        // References such as `REF_TO Int`, kept as one name to be rejected when converting
+        if self.at_keyword("REF_TO") {
+            let start = self.next().expect("keyword was peeked").span;
+            let (data_type, span, _) = self.parse_data_type()?;
+            let name = match data_type {
+                DataType::Named(name) => name,
+                DataType::Struct(_) => "Struct".to_string(),
+            };
+            return Ok((
+                DataType::Named(format!("REF_TO {}", name)),
+                start.to(span),
+                None,
+            ));
+        }
+
+        let (mut name, mut span) = self.expect_name("data type")?;
+
+        // Types with a length, such as String[20]
+        if self.accept(TokenKind::Symbol('[')) {
+            let (length, _) = self.expect_integer("length")?;
+            let end = self.expect(TokenKind::Symbol(']'))?;
+            name = format!("{}[{}]", name, length);
+            span = span.to(end);
+        }
+
+        Ok((DataType::Named(name), span, None))
+    }
+
+    /// Parse the initial value of a member, up to the `;` ending the member.
+    /// Returns one value per element, with repetitions such as `3(0)` expanded
+    fn parse_initializer(&mut self) -> Result<Vec<String>, ParseError> {
+        if self.accept(TokenKind::Symbol('[')) {
+            self.parse_values(']')
+        } else {
+            Ok(vec![self.parse_value()?])
+        }
+    }
+
+    /// Parse comma separated values of an array up to and including the closing symbol
+    fn parse_values(&mut self, close: char) -> Result<Vec<String>, ParseError> {
+        let mut values = vec![];
+
+        loop {
+            let repeat = match (
+                self.peek().map(|token| &token.kind),
+                self.tokens
+                    .get(self.next_index() + 1)
+                    .map(|token| &token.kind),
+            ) {
+                (Some(TokenKind::Number(count)), Some(TokenKind::Symbol('('))) => {
+                    count.parse::<usize>().ok()
+                }
+                _ => None,
+            };
+
+            // Repetitions such as `3(0)` or `2(1, 2)`
+            if let Some(count) = repeat {
+                self.next();
+                self.next();
+                let repeated = self.parse_values(')')?;
+                for _ in 0..count {
+                    values.extend(repeated.iter().cloned());
+                }
+            } else {
+                values.push(self.parse_value()?);
+            }
+
+            if self.accept(TokenKind::Symbol(close)) {
+                return Ok(values);
+            } else if !self.accept(TokenKind::Symbol(',')) {
+                return Err(self.error(&format!("`,` or `{}`", close)));
+            }
+        }
+    }
+
+    /// Parse a single value as written, such as `16#FF`, `'text'` or `(a := 1, b := 2)`
+    fn parse_value(&mut self) -> Result<String, ParseError> {
+        let mut value = String::new();
+        let mut end = None;
+        let mut depth = 0usize;
+
+        loop {
+            match self.peek().map(|token| &token.kind) {
+                None => return Err(self.error("`;`")),
+                Some(TokenKind::Symbol(',' | ';' | ')' | ']')) if depth == 0 => break,
+                Some(TokenKind::Symbol('(' | '[')) => depth += 1,
+                Some(TokenKind::Symbol(')' | ']')) => depth -= 1,
+                _ => {}
+            }
+            let token = self.next().expect("token was peeked");
+            let span = token.span;
+
+            // Values spanning lines or holding comments are kept on one line without them
+            let closing = matches!(token.kind, TokenKind::Symbol(')' | ']' | ','));
+            if end.is_some_and(|end| end < span.start) && !closing {
+                value.push(' ');
+            }
+            value.push_str(&self.source[span.start..span.end]);
+            end = Some(span.end);
+        }
+
+        if value.is_empty() {
+            return Err(self.error("value"));
+        }
+        Ok(value)
+    }
+
+    /// Parse a member up to and including its trailing comment
+    fn parse_member(&mut self) -> Result<MemberDecl, ParseError> {
+        let (name, _) = self.expect_name("member name")?;
+
+        let attributes = if self.at_symbol('{') {
+            self.parse_attributes()?
+        } else {
+            vec![]
+        };
+
+        self.expect(TokenKind::Symbol(':'))?;
+        let (data_type, type_span, dimensions) = self.parse_data_type()?;
+
+        let initial = if self.accept(TokenKind::Assign) {
+            Some(self.parse_initializer()?)
+        } else {
+            None
+        };
+
+        // Nested STRUCTs end with `END_STRUCT;`, which was consumed with its members
+        let end = if matches!(data_type, DataType::Struct(_)) {
+            self.tokens[self.pos - 1].span.end
+        } else {
+            self.expect(TokenKind::Symbol(';'))?.end
+        };
+
+        let mut comments = self.inner_comments(&data_type, type_span, end);
+        comments.extend(self.trailing_comment(end));
+
+        Ok(MemberDecl {
+            name,
+            attributes,
+            type_span,
+            bounds_span: dimensions.as_ref().map(|(_, span)| *span),
+            dimensions: dimensions
+                .map(|(dimensions, _)| dimensions)
+                .unwrap_or_default(),
+            initial,
+            comment: (!comments.is_empty()).then(|| comments.join("\n")),
+            data_type,
+        })
+    }
+
+    /// Parse members up to and including the end keyword, such as `END_STRUCT;`
+    fn parse_members(&mut self, end: &str) -> Result<Vec<MemberDecl>, ParseError> {
+        let mut members = vec![];
+
+        while !self.at_keyword(end) {
+            // A missing end keyword, the end or start of a block must not be read as members
+            if self.peek().is_none() || self.at_block() || self.at_block_end() {
+                return Err(self.error(&format!("`{}`", end)));
+            }
+
+            let leading = self.leading_comments();
+            let start = self.pos;
+            let mut member = match self.parse_member() {
+                Ok(member) => member,
+                Err(err) => {
+                    self.pos = start;
+                    self.skip_member(end);
+                    self.errors.push(err);
+                    continue;
+                }
+            };
+            member.comment = match (leading, member.comment) {
+                (Some(leading), Some(trailing)) => Some(format!("{}\n{}", leading, trailing)),
+                (leading, trailing) => leading.or(trailing),
+            };
+            members.push(member);
+        }
+
+        self.next();
+        self.accept(TokenKind::Symbol(';'));
+        Ok(members)
+    }
+
+    /// Skip a member that could not be parsed, up to and including its `;`.
+    /// Stops before the end keyword of the enclosing structure, the end of the block
+    /// or the next block if the `;` is missing
+    fn skip_member(&mut self, end: &str) {
+        let mut depth = 0usize;
+
+        while self.peek().is_some()
+            && !(depth == 0 && (self.at_keyword(end) || self.at_block() || self.at_block_end()))
+        {
+            match self.next().expect("token was peeked").kind {
+                TokenKind::Ident(ident) if ident.eq_ignore_ascii_case("STRUCT") => depth += 1,
+                TokenKind::Ident(ident) if ident.eq_ignore_ascii_case("END_STRUCT") => {
+                    depth = depth.saturating_sub(1)
+                }
+                TokenKind::Symbol(';') if depth == 0 => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Check if the next token starts a block, such as `TYPE` or `DATA_BLOCK`
+    fn at_block(&self) -> bool {
+        BLOCKS.iter().any(|keyword| self.at_keyword(keyword))
+    }
+
+    /// Check if the next token ends a block, such as `END_TYPE`
+    fn at_block_end(&self) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Ident(ident), .. })
+            if BLOCKS.iter().any(|keyword| ident.eq_ignore_ascii_case(&format!("END_{}", keyword))))
+    }
+
+    /// Check if the next token starts a `VAR_*` section
+    fn at_section(&self) -> bool {
+        SECTIONS.iter().any(|(keyword, _)| self.at_keyword(keyword))
+    }
+
+    /// Parse the header entries of a block, such as TITLE, VERSION, attributes and flags
+    /// like NON_RETAIN, up to the start of its structure
+    fn parse_header(&mut self, decl: &mut TypeDecl, flags: &[&str]) -> Result<(), ParseError> {
+        loop {
+            // A comment in the header describes the block, unless it has a TITLE
+            if let Some(Token {
+                kind: TokenKind::Comment(comment),
+                ..
+            }) = self.tokens.get(self.pos)
+            {
+                if decl.title.is_none() && !comment.is_empty() {
+                    decl.title = Some(comment.clone());
+                }
+                self.pos += 1;
+            } else if self.at_keyword("STRUCT") || self.at_section() || self.at_keyword("BEGIN") {
+                return Ok(());
+            } else if self.at_symbol('{') {
+                decl.attributes = self.parse_attributes()?;
+            } else if self.at_keyword("TITLE") {
+                self.next();
+                self.expect(TokenKind::Symbol('='))?;
+                if let Some(Token {
+                    kind: TokenKind::Text(title),
+                    ..
+                }) = self.peek().cloned()
+                {
+                    self.next();
+                    decl.title = Some(title);
+                }
+            } else if flags.iter().any(|flag| self.at_keyword(flag)) {
+                self.next();
+            } else if let Some(Token {
+                kind: TokenKind::Ident(key),
+                span,
+            }) = self.peek().cloned()
+            {
+                // A name without a value, such as the type of an instance DB
+                if !matches!(
+                    self.tokens.get(self.next_index() + 1),
+                    Some(Token {
+                        kind: TokenKind::Symbol(':'),
+                        ..
+                    })
+                ) && decl.block == BlockKind::DataBlock
+                {
+                    return Ok(());
+                }
+
+                self.next();
+                let end = self
+                    .expect(TokenKind::Symbol(':'))
+                    .map_err(|_| ParseError {
+                        message: format!("expected `STRUCT`, found `{}`", key),
+                        span,
+                        unsupported: false,
+                    })?
+                    .end;
+                let value = self.rest_of_line(end);
+
+                if key.eq_ignore_ascii_case("VERSION") {
+                    decl.version = Some(value);
+                }
+            } else if decl.block == BlockKind::DataBlock
+                && matches!(
+                    self.peek(),
+                    Some(Token {
+                        kind: TokenKind::Quoted(_),
+                        ..
+                    })
+                )
+            {
+                return Ok(());
+            } else {
+                return Err(self.error("`STRUCT`"));
+            }
+        }
+    }
+
+    /// Parse a `VAR_*` section up to and including `END_VAR`
+    fn parse_section(&mut self) -> Result<Section, ParseError> {
+        let mut kind = SECTIONS
+            .iter()
+            .find(|(keyword, _)| self.at_keyword(keyword))
+            .map(|&(_, kind)| kind)
+            .ok_or_else(|| self.error("`VAR`"))?;
+        self.next();
+
+        loop {
+            if self.accept_keyword("CONSTANT") {
+                kind = SectionKind::Constant;
+            } else if !self.accept_keyword("RETAIN") && !self.accept_keyword("NON_RETAIN") {
+                break;
+            }
+        }
+
+        Ok(Section {
+            kind,
+            members: self.parse_members("END_VAR")?,
+        })
+    }
+
+    /// Skip the code or initial values after `BEGIN`, up to and including the end keyword
+    fn skip_body(&mut self, end: &str) -> Result<(), ParseError> {
+        if self.accept_keyword("BEGIN") {
+            self.skip_to(end)
+        } else {
+            self.expect_keyword(end).map(|_| ())
+        }
+    }
+
+    /// Skip everything up to and including the end keyword
+    fn skip_to(&mut self, end: &str) -> Result<(), ParseError> {
+        while !self.at_keyword(end) {
+            if self.next().is_none() {
+                return Err(self.error(&format!("`{}`", end)));
+            }
+        }
+
+        self.next();
+        Ok(())
+    }
+
+    /// Parse a `TYPE` block up to and including `END_TYPE`
+    fn parse_type(&mut self) -> Result<TypeDecl, ParseError> {
+        self.expect_keyword("TYPE")?;
+        let (name, name_span) = self.expect_name("UDT name")?;
+        let mut decl = TypeDecl::new(name, name_span, BlockKind::Type);
+
+        self.parse_header(&mut decl, &[])?;
+        self.expect_keyword("STRUCT")?;
+        decl.members = self.parse_members("END_STRUCT")?;
+        self.expect_keyword("END_TYPE")?;
+        Ok(decl)
+    }
+
+    /// Parse an assignment after `BEGIN` of a data block, up to and including its `;`
+    fn parse_assignment(&mut self) -> Result<Assignment, ParseError> {
+        let mut path = vec![];
+        let start = self.peek().map(|token| token.span);
+
+        loop {
+            let (name, _) = self.expect_name("member name")?;
+            let mut indices = vec![];
+            if self.accept(TokenKind::Symbol('[')) {
+                loop {
+                    indices.push(self.expect_signed("index")?.0);
+                    if self.accept(TokenKind::Symbol(']')) {
+                        break;
+                    }
+                    self.expect(TokenKind::Symbol(','))?;
+                }
+            }
+            path.push((name, indices));
+
+            if !self.accept(TokenKind::Symbol('.')) {
+                break;
+            }
+        }
+
+        self.expect(TokenKind::Assign)?;
+        let value = self.parse_value()?;
+        let end = self.expect(TokenKind::Symbol(';'))?;
+
+        Ok(Assignment {
+            path,
+            value,
+            span: start.map_or(end, |start| start.to(end)),
+        })
+    }
+
+    /// Parse the start values after `BEGIN` of a data block, up to and including the end
+    /// keyword. An assignment that cannot be parsed is skipped and its error kept
+    fn parse_assignments(&mut self, end: &str) -> Result<Vec<Assignment>, ParseError> {
+        let mut assignments = vec![];
+        if !self.accept_keyword("BEGIN") {
+            self.expect_keyword(end)?;
+            return Ok(assignments);
+        }
+
+        while !self.at_keyword(end) {
+            if self.peek().is_none() || self.at_block() || self.at_block_end() {
+                return Err(self.error(&format!("`{}`", end)));
+            }
+
+            let start = self.pos;
+            match self.parse_assignment() {
+                Ok(assignment) => assignments.push(assignment),
+                Err(err) => {
+                    self.pos = start;
+                    self.skip_member(end);
+                    self.errors.push(err);
+                }
+            }
+        }
+
+        self.next();
+        Ok(assignments)
+    }
+
+    /// Parse a `DATA_BLOCK` up to and including `END_DATA_BLOCK`. The structure of a global DB
+    /// is either a `STRUCT` or `VAR` sections, an instance DB names its type instead.
+    /// Start values after `BEGIN` are kept as assignments
+    fn parse_data_block(&mut self) -> Result<TypeDecl, ParseError> {
+        self.expect_keyword("DATA_BLOCK")?;
+        let (name, name_span) = self.expect_name("DB name")?;
+        let mut decl = TypeDecl::new(name, name_span, BlockKind::DataBlock);
+
+        self.parse_header(
+            &mut decl,
+            &["NON_RETAIN", "RETAIN", "READ_ONLY", "UNLINKED"],
+        )?;
+
+        if self.accept_keyword("STRUCT") {
+            decl.members = self.parse_members("END_STRUCT")?;
+        } else if self.at_keyword("VAR") {
+            while self.at_keyword("VAR") {
+                decl.members.append(&mut self.parse_section()?.members);
+            }
+        } else {
+            decl.instance_of = Some(self.expect_name("`STRUCT`, `VAR` or DB type")?.0);
+        }
+
+        decl.assignments = self.parse_assignments("END_DATA_BLOCK")?;
+        Ok(decl)
+    }
+
+    /// Parse a `FUNCTION_BLOCK` or `FUNCTION` up to and including its end keyword,
+    /// keeping the sections of its interface and skipping its code
+    fn parse_interface(&mut self, block: BlockKind) -> Result<TypeDecl, ParseError> {
+        let (keyword, end) = match block {
+            BlockKind::Function => ("FUNCTION", "END_FUNCTION"),
+            _ => ("FUNCTION_BLOCK", "END_FUNCTION_BLOCK"),
+        };
+        self.expect_keyword(keyword)?;
+        let (name, name_span) = self.expect_name("block name")?;
+        let mut decl = TypeDecl::new(name, name_span, block);
+
+        // Return type of a function, such as `: Void`
+        if self.accept(TokenKind::Symbol(':')) {
+            self.parse_data_type()?;
+        }
+
+        self.parse_header(&mut decl, &[])?;
+
+        while self.at_section() {
+            decl.sections.push(self.parse_section()?);
+        }
+
+        self.skip_body(end)?;
+        Ok(decl)
+    }
+}
+
+/// Parse the data type of a member written on its own, such as `Array[0..15] of Bool`.
+/// The members of a `Struct` are not part of it and are left empty
+pub fn parse_type_name(text: &str) -> Result<(DataType, Vec<Dimension>), ParseError> {
+    let mut parser = Parser {
+        source: text,
+        tokens: tokenize(text)?,
+        pos: 0,
+        errors: vec![],
+    };
+    let mut dimensions = vec![];
+
+    if parser.at_keyword("ARRAY") {
+        let start = parser.next().expect("keyword was peeked").span;
+        parser.expect(TokenKind::Symbol('['))?;
+        dimensions = parser.parse_dimensions()?.0;
+        parser.expect(TokenKind::Symbol(']'))?;
+        parser.expect_keyword("OF")?;
+
+        if parser.at_keyword("ARRAY") {
+            return Err(ParseError {
+                message: "arrays of arrays are not supported".to_string(),
+                span: start,
+                unsupported: true,
+            });
+        }
+    }
+
+    let data_type = if parser.accept_keyword("STRUCT") {
+        DataType::Struct(vec![])
+    } else {
+        parser.parse_data_type()?.0
+    };
+
+    if parser.peek().is_some() {
+        return Err(parser.error("end of data type"));
+    }
+
+    Ok((data_type, dimensions))
+}
+
+/// Keywords starting the blocks of a source
+const BLOCKS: [&str; 5] = [
+    "TYPE",
+    "DATA_BLOCK",
+    "FUNCTION_BLOCK",
+    "FUNCTION",
+    "ORGANIZATION_BLOCK",
+];
+
+/// Keywords starting the sections of a block interface
+const SECTIONS: [(&str, SectionKind); 6] = [
+    ("VAR_INPUT", SectionKind::Input),
+    ("VAR_OUTPUT", SectionKind::Output),
+    ("VAR_IN_OUT", SectionKind::InOut),
+    ("VAR_TEMP", SectionKind::Temp),
+    ("VAR_STAT", SectionKind::Static),
+    ("VAR", SectionKind::Static),
+];
+
+/// Parse all type declaring blocks of a source: `TYPE`, `DATA_BLOCK`,
+/// `FUNCTION_BLOCK` and `FUNCTION`. Members and blocks with errors are skipped
+/// and parsing continues with the next one, only invalid tokens fail right away
+pub fn parse(source: &str) -> Result<Parsed, ParseError> {
+    let mut parser = Parser {
+        source,
+        tokens: tokenize(source)?,
+        pos: 0,
+        errors: vec![],
+    };
+    let mut types = vec![];
+
+    while parser.peek().is_some() {
+        let start = parser.pos;
+        let result = if parser.at_keyword("DATA_BLOCK") {
+            parser.parse_data_block().map(Some)
+        } else if parser.at_keyword("TYPE") {
+            parser.parse_type().map(Some)
+        } else if parser.at_keyword("FUNCTION_BLOCK") {
+            parser.parse_interface(BlockKind::FunctionBlock).map(Some)
+        } else if parser.at_keyword("FUNCTION") {
+            parser.parse_interface(BlockKind::Function).map(Some)
+        } else if parser.at_keyword("ORGANIZATION_BLOCK") {
+            // Blocks without an interface to convert, found in SCL sources along with types
+            parser.next();
+            parser.skip_to("END_ORGANIZATION_BLOCK").map(|_| None)
+        } else {
+            Err(parser.error("`TYPE`, `DATA_BLOCK`, `FUNCTION_BLOCK` or `FUNCTION`"))
+        };
+
+        match result {
+            Ok(decl) => types.extend(decl),
+            Err(err) => {
+                parser.errors.push(err);
+
+                // Continue with the next block, moving on even if the error is at its start
+                if parser.pos == start {
+                    parser.next();
+                }
+                while parser.peek().is_some() && !parser.at_block() {
+                    parser.next();
+                }
+            }
+        }
+    }
+
+    Ok(Parsed {
+        types,
+        errors: parser.errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a source that must have no errors
+    fn parse_ok(source: &str) -> Vec<TypeDecl> {
+        let parsed = parse(source).expect("source tokenizes");
+        assert!(
+            parsed.errors.is_empty(),
+            "unexpected errors: {:?}",
+            parsed.errors
+        );
+        parsed.types
+    }
+
+    /// Get the name of a named data type
+    fn type_name(member: &MemberDecl) -> &str {
+        match &member.data_type {
+            DataType::Named(name) => name,
+            DataType::Struct(_) => panic!("{} is a STRUCT", member.name),
+        }
+    }
+
+    #[test]
+    fn parses_type() {
+        let types = parse_ok(
+            "TYPE \"MotorData\"\n\
+             VERSION : 0.1\n\
+             \x20  STRUCT\n\
+             \x20     speed : Real;   // Speed in rpm\n\
+             \x20     name : String[20];\n\
+             \x20     flags : Array[0..15] of Bool;\n\
+             \x20  END_STRUCT;\n\
+             END_TYPE\n",
+        );
+
+        assert_eq!(types.len(), 1);
+        let decl = &types[0];
+        assert_eq!(decl.name, "MotorData");
+        assert_eq!(decl.block, BlockKind::Type);
+        assert_eq!(decl.version.as_deref(), Some("0.1"));
+
+        let names: Vec<&str> = decl.members.iter().map(type_name).collect();
+        assert_eq!(names, ["Real", "String[20]", "Bool"]);
+        assert_eq!(decl.members[0].comment.as_deref(), Some("Speed in rpm"));
+        assert!(matches!(
+            decl.members[2].dimensions[..],
+            [(Bound::Value(0), Bound::Value(15))]
+        ));
+    }
+
+    #[test]
+    fn spans_have_line_and_column() {
+        let source =
+            "TYPE \"Ä_T\"\n   STRUCT\n      a : Int;\n      b : Bool;\n   END_STRUCT;\nEND_TYPE\n";
+        let types = parse_ok(source);
+        let decl = &types[0];
+
+        assert_eq!((decl.name_span.line, decl.name_span.column), (1, 6));
+        assert_eq!(&source[decl.name_span.start..decl.name_span.end], "\"Ä_T\"");
+
+        let span = decl.members[1].type_span;
+        assert_eq!((span.line, span.column), (4, 11));
+        assert_eq!(&source[span.start..span.end], "Bool");
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let source = "TYPE \"T\"\nSTRUCT\n   ä : Int;\nEND_STRUCT;\nEND_TYPE";
+        let span = parse_ok(source)[0].members[0].type_span;
+        assert_eq!((span.line, span.column), (3, 8));
+    }
+
+    #[test]
+    fn reports_errors_with_span() {
+        let parsed =
+            parse("TYPE \"T\"\nSTRUCT\n   a : Int\n   b : Bool;\nEND_STRUCT;\nEND_TYPE").unwrap();
+
+        assert_eq!(parsed.errors.len(), 1);
+        let error = &parsed.errors[0];
+        assert_eq!(error.message, "expected `;`, found `b`");
+        assert_eq!((error.span.line, error.span.column), (4, 4));
+    }
+
+    #[test]
+    fn unterminated_quotes_fail() {
+        let err = parse("TYPE \"T\nSTRUCT").err().expect("tokenizing fails");
+        assert_eq!(err.message, "unterminated quotes");
+        assert_eq!((err.span.line, err.span.column), (1, 6));
+    }
+
+    #[test]
+    fn dollar_escapes_quotes_in_text() {
+        let types = parse_ok(
+            "TYPE \"T\"\nSTRUCT\n   s { S7_SetPoint := 'It$'s' } : String := 'a$'b$$';\nEND_STRUCT;\nEND_TYPE",
+        );
+        let member = &types[0].members[0];
+
+        assert_eq!(member.attributes[0].name, "S7_SetPoint");
+        assert_eq!(member.attributes[0].value, "It$'s");
+        assert_eq!(
+            member.initial.as_deref(),
+            Some(&["'a$'b$$'".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn reads_attributes() {
+        let types = parse_ok(
+            "TYPE \"T\"\n\
+             { S7_Optimized_Access := 'TRUE' }\n\
+             STRUCT\n\
+             \x20  a { ExternalAccessible := 'False'; ExternalWritable := 'False'} : Int;\n\
+             END_STRUCT;\n\
+             END_TYPE",
+        );
+        let decl = &types[0];
+
+        assert_eq!(decl.attributes[0].name, "S7_Optimized_Access");
+        assert_eq!(decl.attributes[0].value, "TRUE");
+        let names: Vec<&str> = decl.members[0]
+            .attributes
+            .iter()
+            .map(|attribute| attribute.name.as_str())
+            .collect();
+        assert_eq!(names, ["ExternalAccessible", "ExternalWritable"]);
+        assert_eq!(decl.members[0].attributes[1].value, "False");
+    }
+
+    #[test]
+    fn reads_title() {
+        let types = parse_ok(
+            "TYPE \"T\"\nTITLE = Motor data: speed (rpm) & 'state'\nVERSION : 0.1\nSTRUCT\n   a : Int;\nEND_STRUCT;\nEND_TYPE",
+        );
+        assert_eq!(
+            types[0].title.as_deref(),
+            Some("Motor data: speed (rpm) & 'state'")
+        );
+        assert_eq!(types[0].version.as_deref(), Some("0.1"));
+    }
+
+    #[test]
+    fn title_continues_on_following_lines() {
+        let types = parse_ok(
+            "TYPE \"T\"\nTITLE = First line\n   second line\nVERSION : 0.1\nSTRUCT\n   a : Int;\nEND_STRUCT;\nEND_TYPE",
+        );
+        assert_eq!(types[0].title.as_deref(), Some("First line\nsecond line"));
+    }
+
+    #[test]
+    fn header_comment_describes_block() {
+        let types =
+            parse_ok("TYPE \"T\"\n// Describes T\nSTRUCT\n   a : Int;\nEND_STRUCT;\nEND_TYPE");
+        assert_eq!(types[0].title.as_deref(), Some("Describes T"));
+    }
+
+    #[test]
+    fn keeps_leading_and_block_comments() {
+        let types = parse_ok(
+            "TYPE \"T\"\nSTRUCT\n   // Leading\n   a : Int; (* Block\n      comment *)\nEND_STRUCT;\nEND_TYPE",
+        );
+        assert_eq!(
+            types[0].members[0].comment.as_deref(),
+            Some("Leading\nBlock\ncomment")
+        );
+    }
+
+    #[test]
+    fn parses_array_bounds_in_any_format() {
+        for text in [
+            "Array[0..9] of Int",
+            "Array [ 0 .. 9 ] of Int",
+            "ARRAY[0 ..9] OF Int",
+            "Array[\n  0..9\n] of Int",
+        ] {
+            let (_, dimensions) = parse_type_name(text).expect(text);
+            assert!(
+                matches!(dimensions[..], [(Bound::Value(0), Bound::Value(9))]),
+                "{}",
+                text
+            );
+        }
+
+        let (_, dimensions) = parse_type_name("Array[-5..+5] of Int").unwrap();
+        assert!(matches!(
+            dimensions[..],
+            [(Bound::Value(-5), Bound::Value(5))]
+        ));
+        assert!(parse_type_name("Array[9..0] of Int").is_err());
+    }
+
+    /// A UDT as TIA Portal exports it, with bounds written in the ways TIA Portal accepts
+    const BOUNDS: &str = r#"TYPE "Buffer"
+TITLE = Receive buffer
+VERSION : 0.1
+//Raw telegram with its header
+   STRUCT
+      data { S7_SetPoint := 'False'} : Array[ 0 .. 255 ] OF Byte;   // Telegram bytes
+      matrix : Array[1..10, 0..31] of Bool;
+      shifted : Array[-10..-1] of Int;
+      items : Array[0.."MAX_ITEMS"] of DInt;
+      wide : Array[0..LAST] of Real;
+   END_STRUCT;
+
+END_TYPE
+"#;
+
+    #[test]
+    fn parses_bounds_of_an_export() {
+        let types = parse_ok(BOUNDS);
+        let members = &types[0].members;
+        let bounds = |index: usize| &members[index].dimensions[..];
+
+        assert!(matches!(bounds(0), [(Bound::Value(0), Bound::Value(255))]));
+        assert_eq!(members[0].comment.as_deref(), Some("Telegram bytes"));
+        assert!(matches!(
+            bounds(1),
+            [
+                (Bound::Value(1), Bound::Value(10)),
+                (Bound::Value(0), Bound::Value(31))
+            ]
+        ));
+        assert!(matches!(bounds(2), [(Bound::Value(-10), Bound::Value(-1))]));
+        assert!(
+            matches!(bounds(3), [(Bound::Value(0), Bound::Constant(name))] if name == "MAX_ITEMS")
+        );
+        assert!(matches!(bounds(4), [(Bound::Value(0), Bound::Constant(name))] if name == "LAST"));
+        assert_eq!(type_name(&members[0]), "Byte");
+    }
+
+    #[test]
+    fn comments_may_hold_any_characters() {
+        let types = parse_ok(
+            "TYPE \"T\"\nSTRUCT\n   a : Int;   // a; b { 'c' \"d\"\n   b : Int;   (* END_STRUCT; } *)\nEND_STRUCT;\nEND_TYPE",
+        );
+        let members = &types[0].members;
+
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].comment.as_deref(), Some("a; b { 'c' \"d\""));
+        assert_eq!(members[1].comment.as_deref(), Some("END_STRUCT; }"));
+    }
+
+    #[test]
+    fn parses_nested_struct() {
+        let types = parse_ok(
+            "TYPE \"T\"\nSTRUCT\n   inner : Struct  // Inner\n      x : Int;\n      y : Array[1..\"MAX\"] of \"Other\";\n   END_STRUCT;\n   after : Bool;\nEND_STRUCT;\nEND_TYPE",
+        );
+        let members = &types[0].members;
+
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].comment.as_deref(), Some("Inner"));
+        let DataType::Struct(inner) = &members[0].data_type else {
+            panic!("inner is not a STRUCT");
+        };
+        assert_eq!(type_name(&inner[1]), "Other");
+        assert!(matches!(
+            &inner[1].dimensions[..],
+            [(Bound::Value(1), Bound::Constant(name))] if name == "MAX"
+        ));
+        assert_eq!(members[1].name, "after");
+    }
+
+    #[test]
+    fn parses_global_data_block() {
+        let types = parse_ok(
+            "DATA_BLOCK \"Settings\"\n\
+             { S7_Optimized_Access := 'TRUE' }\n\
+             VERSION : 0.1\n\
+             NON_RETAIN\n\
+             \x20  VAR\n\
+             \x20     count : Int := 3;\n\
+             \x20  END_VAR\n\
+             BEGIN\n\
+             \x20  count := 5;\n\
+             END_DATA_BLOCK\n",
+        );
+        let decl = &types[0];
+
+        assert_eq!(decl.block, BlockKind::DataBlock);
+        assert_eq!(decl.name, "Settings");
+        assert_eq!(decl.members[0].name, "count");
+        assert!(decl.instance_of.is_none());
+        assert_eq!(decl.assignments[0].path, [("count".to_string(), vec![])]);
+        assert_eq!(decl.assignments[0].value, "5");
+        assert_eq!(decl.assignments[0].span.line, 9);
+    }
+
+    #[test]
+    fn parses_start_values_after_begin() {
+        let parsed = parse(
+            "DATA_BLOCK \"Db\"\nSTRUCT\n   a : Int;\nEND_STRUCT;\nBEGIN\n   \
+             grid[1, -2] := 16#FF;   // comment\n   axis.pos := T#1s;\n   b := ;\n   \
+             name := 'a;b';\nEND_DATA_BLOCK",
+        )
+        .unwrap();
+        let assignments = &parsed.types[0].assignments;
+
+        assert_eq!(assignments.len(), 3);
+        assert_eq!(assignments[0].path, [("grid".to_string(), vec![1, -2])]);
+        assert_eq!(assignments[0].value, "16#FF");
+        assert_eq!(
+            assignments[1].path,
+            [("axis".to_string(), vec![]), ("pos".to_string(), vec![])]
+        );
+        assert_eq!(assignments[1].value, "T#1s");
+        assert_eq!(assignments[2].value, "'a;b'");
+
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].span.line, 8);
+    }
+
+    #[test]
+    fn parses_data_block_struct() {
+        let types =
+            parse_ok("DATA_BLOCK \"Db\"\nSTRUCT\n   a : Int;\nEND_STRUCT;\nBEGIN\nEND_DATA_BLOCK");
+        assert_eq!(types[0].members.len(), 1);
+    }
+
+    #[test]
+    fn parses_instance_data_block() {
+        let types =
+            parse_ok("DATA_BLOCK \"Motor_DB\"\nNON_RETAIN\n\"Motor\"\nBEGIN\nEND_DATA_BLOCK");
+        assert_eq!(types[0].instance_of.as_deref(), Some("Motor"));
+        assert!(types[0].members.is_empty());
+    }
+
+    #[test]
+    fn parses_function_block_sections() {
+        let types = parse_ok(
+            "FUNCTION_BLOCK \"Motor\"\n\
+             VERSION : 0.1\n\
+             \x20  VAR_INPUT\n\
+             \x20     start : Bool;\n\
+             \x20  END_VAR\n\
+             \x20  VAR_OUTPUT\n\
+             \x20     running : Bool;\n\
+             \x20  END_VAR\n\
+             \x20  VAR_IN_OUT\n\
+             \x20     data : \"MotorData\";\n\
+             \x20  END_VAR\n\
+             \x20  VAR RETAIN\n\
+             \x20     count : DInt;\n\
+             \x20  END_VAR\n\
+             \x20  VAR_TEMP\n\
+             \x20     tmp : Int;\n\
+             \x20  END_VAR\n\
+             \x20  VAR CONSTANT\n\
+             \x20     MAX : Int := 10;\n\
+             \x20  END_VAR\n\
+             BEGIN\n\
+             \x20  #running := #start AND NOT (#count > 5);\n\
+             END_FUNCTION_BLOCK\n",
+        );
+        let decl = &types[0];
+
+        assert_eq!(decl.block, BlockKind::FunctionBlock);
+        let kinds: Vec<SectionKind> = decl.sections.iter().map(|section| section.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                SectionKind::Input,
+                SectionKind::Output,
+                SectionKind::InOut,
+                SectionKind::Static,
+                SectionKind::Temp,
+                SectionKind::Constant,
+            ]
+        );
+        assert_eq!(decl.sections[3].members[0].name, "count");
+    }
+
+    #[test]
+    fn parses_function_with_return_type() {
+        let types = parse_ok(
+            "FUNCTION \"Scale\" : Real\nVAR_INPUT\n   raw : Int;\nEND_VAR\nBEGIN\n   #Scale := INT_TO_REAL(#raw);\nEND_FUNCTION",
+        );
+        assert_eq!(types[0].block, BlockKind::Function);
+        assert_eq!(types[0].sections[0].members[0].name, "raw");
+    }
+
+    #[test]
+    fn skips_organization_blocks() {
+        let types = parse_ok(
+            "ORGANIZATION_BLOCK \"Main\"\nBEGIN\n   \"Motor_DB\"();\nEND_ORGANIZATION_BLOCK\n\
+             TYPE \"T\"\nSTRUCT\n   a : Int;\nEND_STRUCT;\nEND_TYPE",
+        );
+        assert_eq!(types.len(), 1);
+        assert_eq!(types[0].name, "T");
+    }
+
+    #[test]
+    fn continues_after_broken_block() {
+        let parsed = parse(
+            "TYPE \"Broken\"\nSTRUCT\n   a : Int;\nEND_TYPE\n\
+             TYPE \"Good\"\nSTRUCT\n   b : Int;\nEND_STRUCT;\nEND_TYPE",
+        )
+        .unwrap();
+
+        assert_eq!(parsed.errors.len(), 1);
+        let names: Vec<&str> = parsed.types.iter().map(|decl| decl.name.as_str()).collect();
+        assert_eq!(names, ["Good"]);
+    }
+
+    #[test]
+    fn rejects_dynamic_arrays() {
+        let parsed = parse("FUNCTION \"F\" : Void\nVAR_IN_OUT\n   a : Array[*] of Int;\nEND_VAR\nBEGIN\nEND_FUNCTION").unwrap();
+        assert!(parsed.errors[0].unsupported);
+        assert!(parsed.types[0].sections[0].members.is_empty());
+    }
+
+    #[test]
+    fn parses_type_names() {
+        let (data_type, dimensions) = parse_type_name("Array[-1..1, 0..3] of String[10]").unwrap();
+        assert!(matches!(data_type, DataType::Named(name) if name == "String[10]"));
+        assert!(matches!(
+            dimensions[..],
+            [
+                (Bound::Value(-1), Bound::Value(1)),
+                (Bound::Value(0), Bound::Value(3))
+            ]
+        ));
+        assert!(parse_type_name("Int Int").is_err());
+    }
+
+    /// Get the initial values of a member
+    fn initial(member: &MemberDecl) -> Vec<&str> {
+        member
+            .initial
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// A UDT as TIA Portal exports it, with initial values spanning lines
+    const INITIALIZERS: &str = r#"TYPE "Recipe"
+VERSION : 0.1
+   STRUCT
+      steps : Array[0..5] of Int := [1, 2,
+            3, 4,   // more steps
+            5, 6];
+      speeds : Array[0..4] of Real := [2(7.5), 3(0.0)];
+      pairs : Array[0..5] of Int := [2(1, 2), 2(0)];
+      limits : Array[0..1] of "Limit" := [(low := 0, high := 10), (low := -5, high := 5)];
+      name : String[10] := 'a, b; (c)';
+      delay : Time := T#1s_500ms;
+   END_STRUCT;
+END_TYPE
+"#;
+
+    #[test]
+    fn parses_multi_line_initializers() {
+        let types = parse_ok(INITIALIZERS);
+        let steps = &types[0].members[0];
+
+        assert_eq!(initial(steps), ["1", "2", "3", "4", "5", "6"]);
+        assert_eq!(steps.comment.as_deref(), Some("more steps"));
+    }
+
+    #[test]
+    fn expands_repetitions() {
+        let types = parse_ok(INITIALIZERS);
+        let members = &types[0].members;
+
+        assert_eq!(initial(&members[1]), ["7.5", "7.5", "0.0", "0.0", "0.0"]);
+        assert_eq!(initial(&members[2]), ["1", "2", "1", "2", "0", "0"]);
+    }
+
+    #[test]
+    fn keeps_struct_initializers_whole() {
+        let types = parse_ok(INITIALIZERS);
+        let members = &types[0].members;
+
+        assert_eq!(
+            initial(&members[3]),
+            ["(low := 0, high := 10)", "(low := -5, high := 5)"]
+        );
+        assert_eq!(initial(&members[4]), ["'a, b; (c)'"]);
+        assert_eq!(initial(&members[5]), ["T#1s_500ms"]);
+    }
+
+    #[test]
+    fn continues_after_malformed_initializer() {
+        let parsed = parse(
+            "TYPE \"T\"\nSTRUCT\n   a : Array[0..1] of Int := [1, 2;\n   b : Int := 3;\n   c : Array[0..1] of Int := [1, , 2];\n   d : Bool;\nEND_STRUCT;\nEND_TYPE",
+        )
+        .unwrap();
+
+        assert_eq!(parsed.errors.len(), 2);
+        assert_eq!(parsed.errors[0].span.line, 3);
+        assert_eq!(parsed.errors[1].span.line, 5);
+
+        let members = &parsed.types[0].members;
+        let names: Vec<&str> = members.iter().map(|member| member.name.as_str()).collect();
+        assert_eq!(names, ["b", "d"]);
+        assert_eq!(initial(&members[0]), ["3"]);
+    }
+
+    #[test]
+    fn malformed_initializer_does_not_swallow_the_block_end() {
+        let parsed =
+            parse("TYPE \"T\"\nSTRUCT\n   a : Int := (1, \nEND_STRUCT;\nEND_TYPE\nTYPE \"U\"\nSTRUCT\n   b : Int;\nEND_STRUCT;\nEND_TYPE")
+                .unwrap();
+
+        let names: Vec<&str> = parsed.types.iter().map(|decl| decl.name.as_str()).collect();
+        assert_eq!(names, ["T", "U"]);
+        assert!(parsed.types[0].members.is_empty());
+        assert_eq!(parsed.errors.len(), 1);
+    }
+}
@@ -0,0 +1,679 @@
+use crate::config::{Config, Naming};
+use crate::diagnostic::Diagnostic;
+use crate::error::Error;
+use crate::udt::{elementary_type, Udt, MAX_NAME_LENGTH};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Load a rename map from a CSV or TOML file, chosen by the file extension.
+/// Keys are Siemens names, either `Udt` or `Udt.Member`, values are the Logix names
+pub fn load_rename_map(path: &Path) -> Result<BTreeMap<String, String>, Error> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| Error::Io(format!("Failed to read {}: {}", path.display(), err)))?;
+
+    let is_toml = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"));
+
+    let map = if is_toml {
+        toml::from_str(&content).map_err(|err| {
+            Error::Config(format!("Invalid rename map {}: {}", path.display(), err))
+        })?
+    } else {
+        parse_csv(path, &content)?
+    };
+
+    log::debug!("Loaded {} rename(s) from {}", map.len(), path.display());
+    Ok(map)
+}
+
+/// Parse `siemens,logix` lines, skipping blank lines and lines starting with `#`
+fn parse_csv(path: &Path, content: &str) -> Result<BTreeMap<String, String>, Error> {
+    let mut map = BTreeMap::new();
+
+    for (num, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+            [from, to] if !from.is_empty() && !to.is_empty() => {
+                map.insert(from.to_string(), to.to_string());
+            }
+            _ => {
+                return Err(Error::Config(format!(
+                    "Invalid rename map {}:{}: expected `siemens_name,logix_name`",
+                    path.display(),
+                    num + 1
+                )))
+            }
+        }
+    }
+
+    Ok(map)
+}
+
+/// Look up the new name for a key, ignoring case
+fn lookup<'a>(map: &'a BTreeMap<String, String>, key: &str) -> Option<&'a str> {
+    map.iter()
+        .find(|(from, _)| from.eq_ignore_ascii_case(key))
+        .map(|(_, to)| to.as_str())
+}
+
+/// Get the new name of a UDT, or the name itself if it is not renamed
+pub fn udt_name(map: &BTreeMap<String, String>, name: &str) -> String {
+    lookup(map, name).unwrap_or(name).to_string()
+}
+
+/// Rename UDTs and their members, updating every member referencing a renamed UDT
+/// and every BOOL packed into a renamed hidden member
+pub fn rename_udts(udts: &mut [Udt], map: &BTreeMap<String, String>) {
+    if map.is_empty() {
+        return;
+    }
+
+    for udt in udts.iter_mut() {
+        let mut renamed_members = vec![];
+
+        for member in &mut udt.members {
+            let key = format!("{}.{}", udt.name, member.name);
+            if let Some(name) = lookup(map, &key) {
+                log::debug!("Renamed {} to {}", key, name);
+                renamed_members.push((member.name.clone(), name.to_string()));
+                member.name = name.to_string();
+            }
+
+            if !elementary_type(&member.data_type) {
+                if let Some(name) = lookup(map, &member.data_type) {
+                    member.data_type = name.to_string();
+                }
+            }
+        }
+
+        for member in &mut udt.members {
+            if let Some(target) = &member.target {
+                if let Some((_, name)) = renamed_members.iter().find(|(from, _)| from == target) {
+                    member.target = Some(name.clone());
+                }
+            }
+        }
+
+        if let Some(name) = lookup(map, &udt.name) {
+            log::debug!("Renamed {} to {}", udt.name, name);
+            udt.name = name.to_string();
+        }
+    }
+}
+
+/// Add the configured prefix and suffix to the names of the UDTs and excluded UDTs,
+/// and to every member referencing one of them
+pub fn decorate_udts(udts: &mut [Udt], excluded: &mut [String], naming: &Naming) {
+    if naming.type_prefix.is_empty() && naming.type_suffix.is_empty() {
+        return;
+    }
+
+    let decorate = |name: &str| format!("{}{}{}", naming.type_prefix, name, naming.type_suffix);
+    let names: Vec<String> = udts
+        .iter()
+        .map(|udt| udt.name.clone())
+        .chain(excluded.iter().cloned())
+        .collect();
+
+    for udt in udts.iter_mut() {
+        udt.name = decorate(&udt.name);
+
+        for member in &mut udt.members {
+            if names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&member.data_type))
+            {
+                member.data_type = decorate(&member.data_type);
+            }
+        }
+    }
+
+    for name in excluded {
+        *name = decorate(name);
+    }
+}
+
+/// Spell out the accented letters common in Siemens names with plain ASCII letters
+fn transliterate(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ä' | 'æ' => "ae",
+        'ö' | 'œ' => "oe",
+        'ü' => "ue",
+        'Ä' | 'Æ' => "Ae",
+        'Ö' | 'Œ' => "Oe",
+        'Ü' => "Ue",
+        'ß' => "ss",
+        'à' | 'á' | 'â' | 'ã' | 'å' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Å' => "A",
+        'ç' => "c",
+        'Ç' => "C",
+        'è' | 'é' | 'ê' | 'ë' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' => "E",
+        'ì' | 'í' | 'î' | 'ï' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' => "I",
+        'ñ' => "n",
+        'Ñ' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ø' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ø' => "O",
+        'ù' | 'ú' | 'û' => "u",
+        'Ù' | 'Ú' | 'Û' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' => "Y",
+        _ => return None,
+    })
+}
+
+/// Turn a Siemens name into a legal Logix name. TIA Portal allows any character in quoted
+/// names, Logix only letters, digits and single underscores, with no digit at the start
+/// and no underscore at the end. Accented letters are transliterated, anything else
+/// becomes an underscore
+pub fn logix_name(name: &str) -> String {
+    let mut output = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if let Some(letters) = transliterate(c) {
+            output.push_str(letters);
+        } else if c.is_ascii_alphanumeric() {
+            output.push(c);
+        } else if !output.ends_with('_') {
+            output.push('_');
+        }
+    }
+
+    let mut output = output.trim_end_matches('_').to_string();
+    if output.is_empty() {
+        output = "Unnamed".to_string();
+    } else if output.starts_with(|c: char| c.is_ascii_digit()) {
+        output.insert(0, '_');
+    }
+    output
+}
+
+/// Words Logix reserves for its data types, Structured Text, components and instruction
+/// members, which Studio 5000 rejects as the names of UDTs and members
+const RESERVED_WORDS: [&str; 68] = [
+    "AND",
+    "BY",
+    "CASE",
+    "DO",
+    "ELSE",
+    "ELSIF",
+    "END_CASE",
+    "END_FOR",
+    "END_IF",
+    "END_REPEAT",
+    "END_WHILE",
+    "EXIT",
+    "FALSE",
+    "FOR",
+    "IF",
+    "MOD",
+    "NOT",
+    "OF",
+    "OR",
+    "REPEAT",
+    "RETURN",
+    "THEN",
+    "TO",
+    "TRUE",
+    "UNTIL",
+    "WHILE",
+    "XOR",
+    "BOOL",
+    "SINT",
+    "INT",
+    "DINT",
+    "LINT",
+    "USINT",
+    "UINT",
+    "UDINT",
+    "ULINT",
+    "REAL",
+    "LREAL",
+    "STRING",
+    "TIMER",
+    "COUNTER",
+    "CONTROL",
+    "MESSAGE",
+    "PID",
+    "ALARM",
+    "AXIS",
+    "TASK",
+    "PROGRAM",
+    "ROUTINE",
+    "MODULE",
+    "CONTROLLER",
+    "EQUIPMENT",
+    "EN",
+    "DN",
+    "TT",
+    "CU",
+    "CD",
+    "OV",
+    "UN",
+    "ER",
+    "EU",
+    "IN",
+    "PRE",
+    "ACC",
+    "POS",
+    "LEN",
+    "FD",
+    "UL",
+];
+
+/// Check if a name is a reserved word of Logix, ignoring case
+pub fn reserved_word(name: &str) -> bool {
+    RESERVED_WORDS
+        .iter()
+        .any(|word| word.eq_ignore_ascii_case(name))
+}
+
+/// Rename the UDTs and members named after a reserved word of Logix with the naming pattern,
+/// updating every reference to them and warning about each renamed name
+pub fn rename_reserved(
+    udts: &mut [Udt],
+    excluded: &mut [String],
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let rename = |name: &str| config.naming.reserved_name.replace("{name}", name);
+    let mut renamed: Vec<(String, String)> = vec![];
+
+    for udt in udts.iter_mut() {
+        if reserved_word(&udt.name) {
+            let name = rename(&udt.name);
+            diagnostics.push(
+                Diagnostic::warning(
+                    "reserved-name",
+                    format!(
+                        "UDT {} is renamed to {}, the name is reserved by Logix",
+                        udt.name, name
+                    ),
+                )
+                .with_span(udt.span)
+                .with_file(udt.file.clone())
+                .with_help("rename the UDT in TIA Portal or with the rename map"),
+            );
+            renamed.push((udt.name.clone(), name.clone()));
+            udt.name = name;
+        }
+
+        let mut renamed_members = vec![];
+        for member in udt.members.iter_mut().filter(|member| !member.hidden) {
+            if reserved_word(&member.name) {
+                let name = rename(&member.name);
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "reserved-name",
+                        format!(
+                            "{}.{} is renamed to {}, the name is reserved by Logix",
+                            udt.name, member.name, name
+                        ),
+                    )
+                    .with_span(member.span)
+                    .with_file(udt.file.clone())
+                    .with_help("rename the member in TIA Portal or with the rename map"),
+                );
+                renamed_members.push((member.name.clone(), name.clone()));
+                member.name = name;
+            }
+        }
+
+        for member in &mut udt.members {
+            if let Some(target) = &member.target {
+                if let Some((_, name)) = renamed_members.iter().find(|(from, _)| from == target) {
+                    member.target = Some(name.clone());
+                }
+            }
+        }
+    }
+
+    for name in excluded.iter_mut().filter(|name| reserved_word(name)) {
+        let new_name = rename(name);
+        renamed.push((name.clone(), new_name.clone()));
+        *name = new_name;
+    }
+
+    for member in udts.iter_mut().flat_map(|udt| &mut udt.members) {
+        if let Some((_, name)) = renamed
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(&member.data_type))
+        {
+            member.data_type = name.clone();
+        }
+    }
+}
+
+/// Shorten a name to the length limit of Logix, ending it with a suffix derived from
+/// the full name so names sharing a beginning stay apart, and the same name is always
+/// shortened the same way. The suffix is derived again until the name is not taken
+fn shortened_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    // FNV-1a, which is stable across Rust versions unlike the standard hasher
+    let hash = |input: &str| {
+        input.bytes().fold(0x811c9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        })
+    };
+
+    (0..)
+        .map(|salt| {
+            let key = if salt == 0 {
+                name.to_string()
+            } else {
+                format!("{}#{}", name, salt)
+            };
+            let suffix = format!("_{:04X}", hash(&key) & 0xFFFF);
+            let kept = MAX_NAME_LENGTH - suffix.len();
+            format!("{}{}", name.chars().take(kept).collect::<String>(), suffix)
+        })
+        .find(|candidate| !taken(candidate))
+        .expect("Ran out of shortened names")
+}
+
+/// Shorten the names of UDTs and members longer than Logix allows, updating every reference
+/// to them and warning about each shortened name. Returns the shortened names as a table
+/// of `Udt` or `Udt.Member` keys and their new names
+pub fn shorten_names(
+    udts: &mut [Udt],
+    excluded: &mut [String],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<(String, String)> {
+    let too_long = |name: &str| name.chars().count() > MAX_NAME_LENGTH;
+    let mut names: Vec<String> = udts
+        .iter()
+        .map(|udt| udt.name.clone())
+        .chain(excluded.iter().cloned())
+        .collect();
+    let mut renamed: Vec<(String, String)> = vec![];
+    let mut table = vec![];
+
+    let mut shorten_type = |name: &str, renamed: &mut Vec<(String, String)>| {
+        if let Some((_, new_name)) = renamed.iter().find(|(from, _)| from == name) {
+            return new_name.clone();
+        }
+        let new_name = shortened_name(name, |candidate| {
+            names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(candidate))
+        });
+        names.push(new_name.clone());
+        renamed.push((name.to_string(), new_name.clone()));
+        new_name
+    };
+
+    for udt in udts.iter_mut() {
+        let original = udt.name.clone();
+
+        if too_long(&udt.name) {
+            let name = shorten_type(&udt.name, &mut renamed);
+            diagnostics.push(
+                Diagnostic::warning(
+                    "name-too-long",
+                    format!(
+                        "UDT {} is shortened to {}, Logix allows at most {} characters",
+                        udt.name, name, MAX_NAME_LENGTH
+                    ),
+                )
+                .with_span(udt.span)
+                .with_file(udt.file.clone())
+                .with_help("choose the shortened name with the rename map"),
+            );
+            table.push((udt.name.clone(), name.clone()));
+            udt.name = name;
+        }
+
+        for index in 0..udt.members.len() {
+            let member = &udt.members[index];
+            // Hidden members are already named within the limit
+            if member.hidden || !too_long(&member.name) {
+                continue;
+            }
+
+            let name = shortened_name(&member.name, |candidate| {
+                udt.members
+                    .iter()
+                    .any(|member| member.name.eq_ignore_ascii_case(candidate))
+            });
+            let member = &udt.members[index];
+            diagnostics.push(
+                Diagnostic::warning(
+                    "name-too-long",
+                    format!(
+                        "{}.{} is shortened to {}, Logix allows at most {} characters",
+                        udt.name, member.name, name, MAX_NAME_LENGTH
+                    ),
+                )
+                .with_span(member.span)
+                .with_file(udt.file.clone())
+                .with_help("choose the shortened name with the rename map"),
+            );
+            table.push((format!("{}.{}", original, member.name), name.clone()));
+            udt.members[index].name = name;
+        }
+    }
+
+    for name in excluded.iter_mut().filter(|name| too_long(name)) {
+        *name = shorten_type(name, &mut renamed);
+    }
+
+    for member in udts.iter_mut().flat_map(|udt| &mut udt.members) {
+        if let Some((_, name)) = renamed
+            .iter()
+            .find(|(from, _)| from.eq_ignore_ascii_case(&member.data_type))
+        {
+            member.data_type = name.clone();
+        }
+    }
+
+    table
+}
+
+/// Get the legal Logix name of a name, with a suffix like a shortened name if it is taken
+fn unique_logix_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    let legal = logix_name(name);
+    if taken(&legal) {
+        shortened_name(&legal, taken)
+    } else {
+        legal
+    }
+}
+
+/// Replace the characters Logix doesn't allow in the names of the UDTs and their members,
+/// and in every reference to them, warning about each changed name. Names that become
+/// the same as another name of their scope get a suffix, legal names are kept as they are
+pub fn sanitize_udts(udts: &mut [Udt], excluded: &mut [String], diagnostics: &mut Vec<Diagnostic>) {
+    for name in excluded.iter_mut() {
+        *name = logix_name(name);
+    }
+
+    let legal = |name: &str| logix_name(name) == name;
+    let mut names: Vec<String> = udts
+        .iter()
+        .map(|udt| udt.name.clone())
+        .filter(|name| legal(name))
+        .chain(excluded.iter().cloned())
+        .collect();
+    let mut renamed: Vec<(String, String)> = vec![];
+
+    for udt in udts.iter_mut().filter(|udt| !legal(&udt.name)) {
+        let name = unique_logix_name(&udt.name, |candidate| {
+            names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(candidate))
+        });
+        diagnostics.push(
+            Diagnostic::warning(
+                "invalid-name",
+                format!(
+                    "UDT {} is renamed to {} to be a legal Logix name",
+                    udt.name, name
+                ),
+            )
+            .with_span(udt.span)
+            .with_file(udt.file.clone())
+            .with_help("rename the UDT in TIA Portal or with the rename map"),
+        );
+        names.push(name.clone());
+        renamed.push((udt.name.clone(), name.clone()));
+        udt.name = name;
+    }
+
+    for udt in udts.iter_mut() {
+        // Hidden members are named after their UDT and are renamed along with it
+        let mut members: Vec<String> = udt
+            .members
+            .iter()
+            .filter(|member| member.hidden || legal(&member.name))
+            .map(|member| logix_name(&member.name))
+            .collect();
+
+        for member in &mut udt.members {
+            if member.hidden {
+                member.name = logix_name(&member.name);
+            } else if !legal(&member.name) {
+                let name = unique_logix_name(&member.name, |candidate| {
+                    members
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(candidate))
+                });
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "invalid-name",
+                        format!(
+                            "{}.{} is renamed to {} to be a legal Logix name",
+                            udt.name, member.name, name
+                        ),
+                    )
+                    .with_span(member.span)
+                    .with_file(udt.file.clone())
+                    .with_help("rename the member in TIA Portal or with the rename map"),
+                );
+                members.push(name.clone());
+                member.name = name;
+            }
+
+            if let Some(target) = &member.target {
+                member.target = Some(logix_name(target));
+            }
+
+            if !elementary_type(&member.data_type) {
+                member.data_type = match renamed
+                    .iter()
+                    .find(|(from, _)| from.eq_ignore_ascii_case(&member.data_type))
+                {
+                    Some((_, name)) => name.clone(),
+                    None => logix_name(&member.data_type),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::udt;
+
+    /// Convert a UDT source with the default config
+    fn convert(source: &str) -> (Vec<Udt>, Vec<Diagnostic>) {
+        let mut diagnostics = vec![];
+        let udts = udt::get_udts(source, &Config::default(), &mut diagnostics).unwrap();
+        (udts, diagnostics)
+    }
+
+    #[test]
+    fn sanitized_names_stay_apart() {
+        let (mut udts, _) = convert(
+            "TYPE \"a-b\"\nSTRUCT\n   \"a-b\" : Int;\n   \"a b\" : Int;\n   a_b_x : Int;\nEND_STRUCT;\nEND_TYPE\n\
+             TYPE \"a b\"\nSTRUCT\n   inner : \"a-b\";\n   other : \"a b\";\nEND_STRUCT;\nEND_TYPE",
+        );
+        let mut diagnostics = vec![];
+        sanitize_udts(&mut udts, &mut [], &mut diagnostics);
+
+        let members: Vec<&str> = udts[0]
+            .members
+            .iter()
+            .map(|member| member.name.as_str())
+            .collect();
+        assert_eq!(members[0], "a_b");
+        assert!(members[1].starts_with("a_b_") && !members[1].eq_ignore_ascii_case("a_b_x"));
+        assert_eq!(members[2], "a_b_x");
+
+        assert_eq!(udts[0].name, "a_b");
+        assert!(udts[1].name.starts_with("a_b_"));
+        assert_eq!(udts[1].members[0].data_type, "a_b");
+        assert_eq!(udts[1].members[1].data_type, udts[1].name);
+        assert_eq!(diagnostics.len(), 4);
+    }
+
+    #[test]
+    fn legal_names_are_kept() {
+        let (mut udts, _) =
+            convert("TYPE \"T\"\nSTRUCT\n   \"a-b\" : Int;\n   a_b : Int;\nEND_STRUCT;\nEND_TYPE");
+        sanitize_udts(&mut udts, &mut [], &mut vec![]);
+
+        assert_ne!(udts[0].members[0].name, "a_b");
+        assert_eq!(udts[0].members[1].name, "a_b");
+    }
+
+    #[test]
+    fn renames_reserved_words() {
+        let (mut udts, _) = convert(
+            "TYPE \"Timer\"\nSTRUCT\n   EN : Bool;\n   preset : DInt;\nEND_STRUCT;\nEND_TYPE\n\
+             TYPE \"T\"\nSTRUCT\n   delay : \"Timer\";\nEND_STRUCT;\nEND_TYPE",
+        );
+        let mut diagnostics = vec![];
+        rename_reserved(&mut udts, &mut [], &Config::default(), &mut diagnostics);
+
+        assert_eq!(udts[0].name, "Timer_R");
+        let member = udts[0]
+            .members
+            .iter()
+            .find(|member| !member.hidden && member.name.starts_with("EN"))
+            .unwrap();
+        assert_eq!(member.name, "EN_R");
+        assert_eq!(udts[0].members.last().unwrap().name, "preset");
+        assert_eq!(udts[1].members[0].data_type, "Timer_R");
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.code == "reserved-name"));
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn shortens_long_names_apart() {
+        let prefix = "Foerderbandgeschwindigkeit_Sollwert_Abschnitt";
+        let (mut udts, _) = convert(&format!(
+            "TYPE \"T\"\nSTRUCT\n   {0}_1 : Int;\n   {0}_2 : Int;\n   short : Int;\nEND_STRUCT;\nEND_TYPE",
+            prefix
+        ));
+        let mut diagnostics = vec![];
+        let table = shorten_names(&mut udts, &mut [], &mut diagnostics);
+
+        let names: Vec<&str> = udts[0]
+            .members
+            .iter()
+            .map(|member| member.name.as_str())
+            .collect();
+        assert!(names[..2]
+            .iter()
+            .all(|name| name.chars().count() == MAX_NAME_LENGTH));
+        assert_ne!(names[0], names[1]);
+        assert_eq!(names[2], "short");
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0], (format!("T.{}_1", prefix), names[0].to_string()));
+
+        // The same name is always shortened the same way
+        assert_eq!(
+            shortened_name(&format!("{}_1", prefix), |_| false),
+            names[0]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use crate::error::Error;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Validate a generated L5X document against an XSD of the format, such as the one installed
+/// with Studio 5000. Uses `xmllint`, which reports every violation with its line
+pub fn validate(xml: &[u8], schema: &Path) -> Result<(), Error> {
+    let mut child = Command::new("xmllint")
+        .args(["--noout", "--schema"])
+        .arg(schema)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            Error::Io(format!(
+                "Failed to run xmllint, which validates against the schema: {}",
+                err
+            ))
+        })?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(xml)
+        .map_err(|err| Error::Io(format!("Failed to pass the L5X to xmllint: {}", err)))?;
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| Error::Io(format!("Failed to run xmllint: {}", err)))?;
+
+    if output.status.success() {
+        log::debug!("L5X is valid against {}", schema.display());
+        return Ok(());
+    }
+
+    // The last line only repeats that validation failed
+    let messages = String::from_utf8_lossy(&output.stderr);
+    let violations: Vec<&str> = messages
+        .lines()
+        .filter(|line| !line.ends_with("fails to validate"))
+        .collect();
+    Err(Error::Xml(format!(
+        "Generated L5X does not match the schema {}:\n{}",
+        schema.display(),
+        violations.join("\n")
+    )))
+}
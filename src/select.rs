@@ -0,0 +1,54 @@
+use crate::error::Error;
+use crate::udt::Udt;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{MultiSelect, Select};
+use std::io::{self, IsTerminal};
+
+/// Let the user pick which UDTs to emit and which of them is the target.
+/// Returns the chosen UDTs with the target last, where `generate` expects the parent
+pub fn select_udts(udts: Vec<Udt>) -> Result<Vec<Udt>, Error> {
+    if !io::stderr().is_terminal() {
+        return Err(Error::Usage(
+            "Interactive selection requires a terminal".to_string(),
+        ));
+    }
+
+    let theme = ColorfulTheme::default();
+    let items = udts
+        .iter()
+        .map(|udt| {
+            let members = udt.members.iter().filter(|member| !member.hidden).count();
+            format!("{} ({} members)", udt.name, members)
+        })
+        .collect::<Vec<_>>();
+
+    let chosen = MultiSelect::with_theme(&theme)
+        .with_prompt("Select the UDTs to emit")
+        .items(&items)
+        .defaults(&vec![true; items.len()])
+        .interact()
+        .map_err(|err| Error::Io(format!("Failed to read selection: {}", err)))?;
+
+    if chosen.is_empty() {
+        return Err(Error::Usage("No UDTs selected".to_string()));
+    }
+
+    let target = Select::with_theme(&theme)
+        .with_prompt("Select the target UDT")
+        .items(chosen.iter().map(|&index| &items[index]))
+        .default(chosen.len() - 1)
+        .interact()
+        .map_err(|err| Error::Io(format!("Failed to read selection: {}", err)))?;
+
+    let mut selected = udts
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| chosen.contains(index))
+        .map(|(_, udt)| udt)
+        .collect::<Vec<_>>();
+
+    let parent = selected.remove(target);
+    selected.push(parent);
+
+    Ok(selected)
+}
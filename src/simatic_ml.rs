@@ -0,0 +1,373 @@
+use crate::diagnostic::Span;
+use crate::parser::{
+    self, Attribute, BlockKind, DataType, MemberDecl, ParseError, Parsed, Section, SectionKind,
+    TypeDecl,
+};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// An element of a SimaticML document with everything inside it
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+    span: Span,
+}
+
+impl Element {
+    fn new(name: String, attributes: Vec<(String, String)>, span: Span) -> Element {
+        Element {
+            name,
+            attributes,
+            children: vec![],
+            text: String::new(),
+            span,
+        }
+    }
+
+    /// Get the value of an attribute
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get the first child element with the name
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Get all child elements with the name
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Follow a path of child elements
+    fn path(&self, path: &[&str]) -> Option<&Element> {
+        path.iter()
+            .try_fold(self, |element, name| element.child(name))
+    }
+
+    /// Get the text of a child element following a path
+    fn path_text(&self, path: &[&str]) -> Option<&str> {
+        self.path(path).map(|element| element.text.as_str())
+    }
+}
+
+/// Pick the text in the language, such as `de-DE` or just `de`, from texts with their language.
+/// Falls back to the first text if no language is given or there is no text in it
+fn pick_language<'a>(
+    texts: impl Iterator<Item = (&'a str, &'a str)>,
+    language: Option<&str>,
+) -> Option<&'a str> {
+    let texts: Vec<(&str, &str)> = texts.filter(|(_, text)| !text.is_empty()).collect();
+
+    let matches = |lang: &str, language: &str| {
+        lang.eq_ignore_ascii_case(language)
+            || lang
+                .split_once('-')
+                .is_some_and(|(prefix, _)| prefix.eq_ignore_ascii_case(language))
+    };
+
+    language
+        .and_then(|language| texts.iter().find(|(lang, _)| matches(lang, language)))
+        .or(texts.first())
+        .map(|(_, text)| *text)
+}
+
+/// Turn an XML start tag into an element without children
+fn start_element(
+    source: &str,
+    tag: &BytesStart,
+    start: usize,
+    end: usize,
+) -> Result<Element, ParseError> {
+    let mut attributes = vec![];
+
+    for attribute in tag.attributes() {
+        let attribute = attribute.map_err(|err| xml_error(source, start, end, err.into()))?;
+        let value = attribute
+            .unescape_value()
+            .map_err(|err| xml_error(source, start, end, err))?;
+        attributes.push((
+            String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned(),
+            value.into_owned(),
+        ));
+    }
+
+    Ok(Element::new(
+        String::from_utf8_lossy(tag.local_name().as_ref()).into_owned(),
+        attributes,
+        Span::new(source, start, end),
+    ))
+}
+
+/// Error for XML that is not well-formed
+fn xml_error(source: &str, start: usize, end: usize, err: quick_xml::Error) -> ParseError {
+    ParseError {
+        message: format!("invalid XML: {}", err),
+        span: Span::new(source, start, end),
+        unsupported: false,
+    }
+}
+
+/// Read the whole document into a tree of elements below an unnamed root
+fn read_document(source: &str) -> Result<Element, ParseError> {
+    let mut reader = Reader::from_str(source);
+    reader.trim_text(true);
+    let mut stack = vec![Element::new(String::new(), vec![], Span::new(source, 0, 0))];
+
+    loop {
+        let start = reader.buffer_position();
+        let event = reader
+            .read_event()
+            .map_err(|err| xml_error(source, start, reader.buffer_position(), err))?;
+        let end = reader.buffer_position();
+        // Whitespace before a tag is trimmed without an event of its own
+        let start = end - source[start..end].trim_start().len();
+
+        match event {
+            Event::Start(tag) => stack.push(start_element(source, &tag, start, end)?),
+            Event::Empty(tag) => {
+                let element = start_element(source, &tag, start, end)?;
+                stack
+                    .last_mut()
+                    .expect("root is never popped")
+                    .children
+                    .push(element);
+            }
+            Event::End(_) => {
+                // The reader rejects end tags without a start tag, so the root is never popped
+                let element = stack.pop().expect("end tags match start tags");
+                stack
+                    .last_mut()
+                    .expect("root is never popped")
+                    .children
+                    .push(element);
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|err| xml_error(source, start, end, err))?;
+                stack
+                    .last_mut()
+                    .expect("root is never popped")
+                    .text
+                    .push_str(&text);
+            }
+            Event::CData(text) => {
+                let text = String::from_utf8_lossy(&text).into_owned();
+                stack
+                    .last_mut()
+                    .expect("root is never popped")
+                    .text
+                    .push_str(&text);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    match stack.pop() {
+        Some(root) if stack.is_empty() => Ok(root),
+        _ => Err(ParseError {
+            message: "unexpected end of XML".to_string(),
+            span: Span::new(source, source.len(), source.len()),
+            unsupported: false,
+        }),
+    }
+}
+
+/// Convert a `Member` element and the members nested inside it.
+/// Returns None if its data type cannot be parsed, adding the error to the parse result
+fn read_member(
+    element: &Element,
+    parsed: &mut Parsed,
+    language: Option<&str>,
+) -> Option<MemberDecl> {
+    let name = element.attribute("Name").unwrap_or_default().to_string();
+    let type_name = element.attribute("Datatype").unwrap_or_default();
+
+    // Errors in the data type point at the member, the data type is only an attribute
+    let (mut data_type, dimensions) = match parser::parse_type_name(type_name) {
+        Ok(data_type) => data_type,
+        Err(err) => {
+            let err = ParseError {
+                message: format!("{}: {}", name, err.message),
+                span: element.span,
+                unsupported: err.unsupported,
+            };
+            parsed.errors.push(err);
+            return None;
+        }
+    };
+
+    if let DataType::Struct(members) = &mut data_type {
+        for child in element.children("Member") {
+            members.extend(read_member(child, parsed, language));
+        }
+    }
+
+    let attributes = element
+        .child("AttributeList")
+        .map(|list| {
+            list.children
+                .iter()
+                .filter_map(|attribute| {
+                    Some(Attribute {
+                        name: attribute.attribute("Name")?.to_string(),
+                        value: attribute.text.clone(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Some(MemberDecl {
+        name,
+        attributes,
+        data_type,
+        type_span: element.span,
+        bounds_span: (!dimensions.is_empty()).then_some(element.span),
+        dimensions,
+        initial: element
+            .child("StartValue")
+            .map(|value| vec![value.text.clone()]),
+        comment: element
+            .child("Comment")
+            .and_then(|comment| {
+                let texts = comment.children("MultiLanguageText").map(|text| {
+                    (
+                        text.attribute("Lang").unwrap_or_default(),
+                        text.text.as_str(),
+                    )
+                });
+                pick_language(texts, language)
+            })
+            .map(str::to_string),
+    })
+}
+
+/// Get the kind of a section of an interface from its name
+fn section_kind(name: &str) -> Option<SectionKind> {
+    match name {
+        "Input" => Some(SectionKind::Input),
+        "Output" => Some(SectionKind::Output),
+        "InOut" => Some(SectionKind::InOut),
+        "Static" | "None" => Some(SectionKind::Static),
+        "Temp" => Some(SectionKind::Temp),
+        "Constant" => Some(SectionKind::Constant),
+        _ => None,
+    }
+}
+
+/// Convert a block element, such as `SW.Types.PlcStruct`, into a type declaration
+fn read_block(
+    element: &Element,
+    block: BlockKind,
+    parsed: &mut Parsed,
+    language: Option<&str>,
+) -> Result<TypeDecl, ParseError> {
+    let attributes = element.child("AttributeList");
+    let name = attributes
+        .and_then(|list| list.child("Name"))
+        .map(|name| name.text.clone())
+        .ok_or_else(|| ParseError {
+            message: format!("{} has no name", element.name),
+            span: element.span,
+            unsupported: false,
+        })?;
+
+    let mut decl = TypeDecl::new(name, element.span, block);
+    decl.version = attributes
+        .and_then(|list| list.child("HeaderVersion"))
+        .map(|version| version.text.clone());
+    decl.title = element
+        .child("ObjectList")
+        .into_iter()
+        .flat_map(|list| list.children("MultilingualText"))
+        .find(|text| text.attribute("CompositionName") == Some("Title"))
+        .and_then(|title| {
+            let texts = title
+                .path(&["ObjectList"])
+                .into_iter()
+                .flat_map(|list| list.children("MultilingualTextItem"))
+                .map(|item| {
+                    (
+                        item.path_text(&["AttributeList", "Culture"])
+                            .unwrap_or_default(),
+                        item.path_text(&["AttributeList", "Text"])
+                            .unwrap_or_default(),
+                    )
+                });
+            pick_language(texts, language)
+        })
+        .map(str::to_string);
+    decl.instance_of = attributes
+        .and_then(|list| list.child("InstanceOfName"))
+        .map(|name| name.text.clone());
+
+    let sections = attributes
+        .and_then(|list| list.path(&["Interface", "Sections"]))
+        .into_iter()
+        .flat_map(|sections| sections.children("Section"));
+
+    for section in sections {
+        let Some(kind) = section.attribute("Name").and_then(section_kind) else {
+            continue;
+        };
+        let mut members = vec![];
+        for member in section.children("Member") {
+            members.extend(read_member(member, parsed, language));
+        }
+
+        match block {
+            BlockKind::FunctionBlock | BlockKind::Function => {
+                decl.sections.push(Section { kind, members })
+            }
+            _ if kind == SectionKind::Static => decl.members.extend(members),
+            _ => {}
+        }
+    }
+
+    Ok(decl)
+}
+
+/// Check if a source is a SimaticML document rather than plain text
+pub fn is_simatic_ml(source: &str) -> bool {
+    source
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .starts_with('<')
+}
+
+/// Parse the PLC data types, DBs, FBs and FCs of a SimaticML document
+/// exported with TIA Portal Openness. Members and blocks with errors are skipped,
+/// only an invalid document fails right away. Comments and titles are taken in the language
+/// if they are written in several languages
+pub fn parse(source: &str, language: Option<&str>) -> Result<Parsed, ParseError> {
+    let root = read_document(source)?;
+    let document = root.child("Document").unwrap_or(&root);
+    let mut parsed = Parsed {
+        types: vec![],
+        errors: vec![],
+    };
+
+    for element in &document.children {
+        let block = match element.name.as_str() {
+            "SW.Types.PlcStruct" => BlockKind::Type,
+            "SW.Blocks.GlobalDB" | "SW.Blocks.InstanceDB" => BlockKind::DataBlock,
+            "SW.Blocks.FB" => BlockKind::FunctionBlock,
+            "SW.Blocks.FC" => BlockKind::Function,
+            _ => continue,
+        };
+        match read_block(element, block, &mut parsed, language) {
+            Ok(decl) => parsed.types.push(decl),
+            Err(err) => parsed.errors.push(err),
+        }
+    }
+
+    Ok(parsed)
+}
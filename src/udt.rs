@@ -1,55 +1,172 @@
-use regex::{Captures, Regex, RegexBuilder};
+use crate::config::{
+    BitStrings, BoolArrays, BoolPacking, Chars, Config, DefaultString, Dtl, ExternalAccess,
+    InterfaceLayout, LowerBounds, Radix, SystemTypes, UnknownTypes, WideStrings,
+};
+use crate::data;
+use crate::diagnostic::{Diagnostic, Span};
+use crate::error::Error;
+use crate::l5x;
+use crate::parser::{
+    self, BlockKind, Bound, DataType, MemberDecl, ParseError, SectionKind, TypeDecl,
+};
+use crate::simatic_ml;
+use glob::{MatchOptions, Pattern};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
+#[derive(Clone)]
 pub struct UdtMember {
     pub name: String,
     pub description: Option<String>,
     pub data_type: String,
     pub array_bounds: Option<(isize, isize)>,
-    pub external_read: bool,
-    pub external_write: bool,
+    /// Attributes such as `ExternalWritable` or `S7_SetPoint`, with their values as written
+    pub metadata: BTreeMap<String, String>,
     pub hidden: bool,
     pub target: Option<String>,
     pub bit_num: Option<usize>,
+    /// Location of the member's data type in the input, if it was parsed from it
+    pub span: Option<Span>,
+    /// Initial value as written in the input, with one value per element if it is an array
+    pub default: Option<Vec<String>>,
+    /// Radix from the config, overriding the one chosen from the data type
+    pub radix: Option<Radix>,
+    /// External access from the config, overriding the one from the attributes
+    pub access: Option<ExternalAccess>,
+    /// TIA Portal type the member was converted from, if it was parsed from the input
+    pub source: Option<SourceType>,
 }
 
+/// TIA Portal type of a member, with the declared element count if it is an array
+#[derive(Clone)]
+pub struct SourceType {
+    pub name: String,
+    pub count: Option<usize>,
+}
+
+#[derive(Clone)]
 pub struct Udt {
     pub name: String,
     pub description: Option<String>,
-    pub _version: String,
+    pub version: String,
     pub members: Vec<UdtMember>,
+    /// Location of the UDT's name in the input, if it was parsed from it
+    pub span: Option<Span>,
+    /// File the UDT was parsed from, if known
+    pub file: Option<PathBuf>,
+    /// The UDT holds the structure of a global DB
+    pub data_block: bool,
+    /// The UDT is a custom length string, with the LEN and DATA members of one
+    pub string: bool,
+    /// Block and section of the interface the UDT was split from, for Add-On Instructions
+    pub section: Option<(String, SectionKind)>,
+}
+
+impl UdtMember {
+    /// Get the value of an attribute, ignoring case
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Get the value of the attributes, false if any of them is set to false,
+    /// or None if none of them is set
+    fn attribute_bool(&self, names: &[&str]) -> Option<bool> {
+        let values: Vec<bool> = names
+            .iter()
+            .filter_map(|name| self.attribute(name))
+            .map(|value| !value.trim().eq_ignore_ascii_case("false"))
+            .collect();
+        (!values.is_empty()).then(|| values.iter().all(|value| *value))
+    }
+
+    /// Determine the external access from the accessibility attributes. Attributes that are
+    /// not set default to true as in TIA Portal, members without any take the default access
+    pub fn external_access(&self, default: ExternalAccess) -> ExternalAccess {
+        if self.hidden {
+            return ExternalAccess::None;
+        }
+        if let Some(access) = self.access {
+            return access;
+        }
+
+        let readable = self.attribute_bool(&ACCESSIBLE_ATTRIBUTES);
+        let writable = self.attribute_bool(&["ExternalWritable"]);
+
+        match (readable, writable) {
+            (None, None) => default,
+            (Some(false), _) => ExternalAccess::None,
+            (_, Some(false)) => ExternalAccess::ReadOnly,
+            _ => ExternalAccess::ReadWrite,
+        }
+    }
+
+    /// Number of elements in Logix, if the member is an array.
+    /// Logix arrays start at 0, so the bounds are shifted by the lower bound
+    pub fn element_count(&self) -> Option<usize> {
+        self.array_bounds
+            .map(|(lower, upper)| (upper - lower + 1) as usize)
+    }
+}
+
+impl Udt {
+    /// Get the description with the VERSION of the UDT appended,
+    /// so converted types remain traceable to their Siemens revision
+    pub fn versioned_description(&self) -> Option<String> {
+        match (&self.description, self.version.is_empty()) {
+            (description, true) => description.clone(),
+            (Some(description), false) => Some(format!(
+                "{} (TIA Portal version {})",
+                description, self.version
+            )),
+            (None, false) => Some(format!("TIA Portal version {}", self.version)),
+        }
+    }
 }
 
 /// Target numbers and bit numbers for bool member variables
 pub struct BoolTargets {
     pub target_num: usize,
     pub bit_num: usize,
+    /// Number of bits of each hidden member
+    pub bits: usize,
 }
 
 impl BoolTargets {
-    /// Return new BoolTargets with values of 0
-    pub fn new() -> BoolTargets {
+    /// Return new BoolTargets with values of 0 for hidden members of the given number of bits
+    pub fn new(bits: usize) -> BoolTargets {
         BoolTargets {
             target_num: 0,
             bit_num: 0,
+            bits,
         }
     }
 
-    /// Increment bit_num, and reset it to 0 and increment target_num when it reaches 7
+    /// Increment bit_num, and reset it to 0 and increment target_num when it reaches the last bit
     pub fn inc(&mut self) {
-        if self.bit_num >= 7 {
+        if self.bit_num + 1 >= self.bits {
             self.bit_num = 0;
             self.target_num += 1;
         } else {
             self.bit_num += 1;
         }
     }
+
+    /// Start a new hidden member, unless no bits of the current one are assigned yet
+    pub fn next(&mut self) {
+        if self.bit_num > 0 {
+            self.bit_num = 0;
+            self.target_num += 1;
+        }
+    }
 }
 
-// TODO: add functionality to automate adding new string data types
 /// Converts the syntax for custom length strings to a valid syntax for Rockwell.
-/// However, custom length strings must be separately defined data types
+/// The custom length string data types are added by `add_string_types`
 pub fn reformat_string(input: &str) -> String {
-    if let Some(_) = input.to_uppercase().find("STRING[") {
+    if input.to_uppercase().contains("STRING[") {
         let end = input.find("]").expect("Invalid STRING type format");
         let mut output = "STRING_".to_string();
         output.push_str(&input[7..end]);
@@ -59,6 +176,13 @@ pub fn reformat_string(input: &str) -> String {
     }
 }
 
+/// Checks if data type is a custom length string such as STRING_32
+pub fn custom_string_type(inp: &str) -> bool {
+    inp.to_uppercase()
+        .strip_prefix("STRING_")
+        .is_some_and(|length| !length.is_empty() && length.chars().all(|c| c.is_ascii_digit()))
+}
+
 /// Checks if data type should use decimal radix
 pub fn numeric_type(inp: &str) -> bool {
     [
@@ -68,20 +192,151 @@ pub fn numeric_type(inp: &str) -> bool {
     .contains(&inp.to_uppercase().as_str())
 }
 
-/// Checks if data type should use character radix
-pub fn char_type(inp: &str) -> bool {
-    inp.to_uppercase() == "CHAR" || "STRING".contains(inp.to_uppercase().as_str())
+/// Checks if data type is built into Studio 5000 and needs no DataType definition
+pub fn elementary_type(inp: &str) -> bool {
+    numeric_type(inp)
+        || ["STRING", "LDT", "TIMER", "COUNTER"].contains(&inp.to_uppercase().as_str())
+}
+
+/// Siemens system types of timers, counters and DTL, which are data types without a source
+enum SystemType {
+    /// IEC timer with the type of its time values
+    Timer(&'static str),
+    /// IEC counter with the type of its count values
+    Counter(String),
+    /// Date and time with its individual fields
+    Dtl,
+}
+
+impl SystemType {
+    /// Check if the config converts the system type to a generated UDT
+    fn generated(&self, config: &Config) -> bool {
+        match self {
+            SystemType::Timer(_) | SystemType::Counter(_) => {
+                config.system_types == SystemTypes::Udt
+            }
+            SystemType::Dtl => config.dtl == Dtl::Udt,
+        }
+    }
+}
+
+/// Value types of the IEC counters, with the prefix of their `IEC_*COUNTER` type
+const COUNTER_TYPES: [(&str, &str); 8] = [
+    ("SINT", "S"),
+    ("INT", ""),
+    ("DINT", "D"),
+    ("LINT", "L"),
+    ("USINT", "US"),
+    ("UINT", "U"),
+    ("UDINT", "UD"),
+    ("ULINT", "UL"),
+];
+
+/// Get the kind of a system type, such as IEC_TIMER, TON_TIME, CTU_INT or DTL
+fn system_type(name: &str) -> Option<SystemType> {
+    let name = name.to_uppercase();
+
+    match name.as_str() {
+        "DTL" => return Some(SystemType::Dtl),
+        "IEC_TIMER" | "TON_TIME" | "TOF_TIME" | "TP_TIME" | "TONR_TIME" => {
+            return Some(SystemType::Timer("Time"))
+        }
+        "IEC_LTIMER" | "TON_LTIME" | "TOF_LTIME" | "TP_LTIME" | "TONR_LTIME" => {
+            return Some(SystemType::Timer("LTime"))
+        }
+        _ => {}
+    }
+
+    COUNTER_TYPES.iter().find_map(|(value_type, prefix)| {
+        let is_counter = name == format!("IEC_{}COUNTER", prefix)
+            || ["CTU", "CTD", "CTUD"]
+                .iter()
+                .any(|counter| name == format!("{}_{}", counter, value_type));
+        is_counter.then(|| SystemType::Counter(value_type.to_string()))
+    })
+}
+
+/// Get the source of a UDT with the members of a system type
+fn system_type_source(name: &str, system_type: &SystemType) -> String {
+    let members = match system_type {
+        SystemType::Timer(time) => format!("PT : {time}; ET : {time}; IN : Bool; Q : Bool;"),
+        SystemType::Counter(count) => format!(
+            "CU : Bool; CD : Bool; R : Bool; LD : Bool; QU : Bool; QD : Bool; \
+             PV : {count}; CV : {count};"
+        ),
+        SystemType::Dtl => "YEAR : UInt; MONTH : USInt; DAY : USInt; WEEKDAY : USInt; \
+             HOUR : USInt; MINUTE : USInt; SECOND : USInt; NANOSECOND : UDInt;"
+            .to_string(),
+    };
+
+    format!(
+        "TYPE \"{}\"\nTITLE = Members of the TIA Portal system type {}\nSTRUCT {} END_STRUCT;\nEND_TYPE",
+        name, name, members
+    )
+}
+
+/// Add a UDT with the members of every system type used by the UDTs that the config
+/// converts to a generated UDT, in front of them
+fn add_system_types(udts: Vec<Udt>, config: &Config) -> Result<Vec<Udt>, Error> {
+    let mut system_udts: Vec<Udt> = vec![];
+
+    for member in udts.iter().flat_map(|udt| &udt.members) {
+        let Some(system_type) =
+            system_type(&member.data_type).filter(|system_type| system_type.generated(config))
+        else {
+            continue;
+        };
+        let defined = udts
+            .iter()
+            .chain(&system_udts)
+            .any(|udt| udt.name.eq_ignore_ascii_case(&member.data_type));
+
+        if !defined {
+            log::debug!("Generated UDT {} for a system type", member.data_type);
+            let source = system_type_source(&member.data_type, &system_type);
+            system_udts.append(&mut get_udts(&source, config, &mut vec![])?);
+        }
+    }
+
+    system_udts.extend(udts);
+    Ok(system_udts)
 }
 
 // TODO: find a way to do this without multiple String allocations (fixed length strings maybe?)
-/// Converts common elementary types from Siemens to an equivalent for Rockwell
-pub fn convert_type(input: &str) -> String {
+/// Converts common elementary types from Siemens to an equivalent for Rockwell,
+/// unless the config overrides the conversion.
+///
+/// Date and time types keep their value as a plain number:
+/// TIME, TOD and S5TIME become DINT milliseconds, LTIME and LTOD become LINT nanoseconds,
+/// DATE becomes DINT days since 1990-01-01 and DATE_AND_TIME becomes LINT holding its BCD bytes.
+/// CHAR becomes SINT, as Logix has no character type
+pub fn convert_type(input: &str, config: &Config) -> String {
+    if let Some(data_type) = config.type_override(input) {
+        return data_type.to_string();
+    }
+
+    if config.system_types == SystemTypes::Predefined {
+        match system_type(input) {
+            Some(SystemType::Timer(_)) => return "TIMER".to_string(),
+            Some(SystemType::Counter(_)) => return "COUNTER".to_string(),
+            Some(SystemType::Dtl) | None => {}
+        }
+    }
+
+    let signed = config.bit_strings == BitStrings::Signed;
+
     match input.to_uppercase().as_str() {
+        "BYTE" if signed => "SINT".to_string(),
+        "WORD" if signed => "INT".to_string(),
+        "DWORD" if signed => "DINT".to_string(),
+        "LWORD" if signed => "LINT".to_string(),
         "BYTE" => "USINT".to_string(),
         "WORD" => "UINT".to_string(),
         "DWORD" => "UDINT".to_string(),
         "LWORD" => "ULINT".to_string(),
-        "TIME" => "DINT".to_string(),
+        "TIME" | "S5TIME" | "DATE" | "TOD" | "TIME_OF_DAY" => "DINT".to_string(),
+        "LTIME" | "LTOD" | "LTIME_OF_DAY" | "DT" | "DATE_AND_TIME" => "LINT".to_string(),
+        "BOOL" => "BOOL".to_string(),
         "SINT" => "SINT".to_string(),
         "INT" => "INT".to_string(),
         "DINT" => "DINT".to_string(),
@@ -93,208 +348,1823 @@ pub fn convert_type(input: &str) -> String {
         "REAL" => "REAL".to_string(),
         "LREAL" => "LREAL".to_string(),
         "STRING" => "STRING".to_string(),
-        "CHAR" => "CHAR".to_string(),
+        "CHAR" => "SINT".to_string(),
+        "DTL" if config.dtl == Dtl::Udt => "DTL".to_string(),
         "DTL" => "LDT".to_string(),
         &_ => reformat_string(input),
     }
 }
 
-// These regex patterns were made at https://regex101.com/ using the Rust flavor
-/// Regex pattern for parsing the head and body from exported UDTs from TIA Portal
-pub fn build_udt_regex() -> Regex {
-    RegexBuilder::new(
-        r#"TYPE\s+"(?<udt_type>\S*)"\s*(?:TITLE\s*=\s*(?<udt_title>[\S\s]*?)\n)?
-            (?:VERSION\s*:\s*(?<udt_version>[\s\S]*?)\n)[\s\S]*?STRUCT
-            (?<udt_body>[\s\S]*?)END_STRUCT;?[\s\S]*?END_TYPE"#,
-    )
-    .case_insensitive(true)
-    .multi_line(true)
-    .ignore_whitespace(true)
-    .build()
-    .expect("Invalid Regex pattern!")
-}
-
-// TODO: Kill it with fire!
-/// Regex pattern for parsing member variables from the body of an exported UDT from TIA Portal
-pub fn build_member_regex() -> Regex {
-    RegexBuilder::new(
-                r#"\s*"?(?<member_name>[a-z0-9_]*)"?\s*?(?:\{(?:\s*?ExternalAccessible\s*?:=\s*?'
-                (?<ext_acs>[a-z]*?)';)?(?:\s*?ExternalVisible\s*?:=\s*?'(?<ext_vis>[a-z]*?)';)?
-                (?:\s*?ExternalWritable\s*?:=\s*?'(?<ext_wrt>[a-z]*?)')?[\s\S]*?})?\s*?:\s*?(?:Array\[
-                (?<bound_lower>[[:digit:]]+)\.\.(?<bound_upper>[[:digit:]])+\]\s*?of\s+?)?"?
-                (?<member_type>[a-z1-9_]*)"?(?:\s*?:=\s*?[\s\S]*?)?;\s*?(?://\s*
-                (?<member_description>[\s\S]*?))?\n"#
-        )
-        .case_insensitive(true)
-        .multi_line(true)
-        .ignore_whitespace(true)
-        .build()
-        .expect("Invalid regex pattern!")
-}
+/// Attributes hiding a member from HMIs and other external access, including the names
+/// used before TIA Portal V13
+const ACCESSIBLE_ATTRIBUTES: [&str; 4] = [
+    "ExternalAccessible",
+    "S7_HMI_Accessible",
+    "ExternalVisible",
+    "S7_HMI_Visible",
+];
 
-pub fn get_udt_description(udt_str: &Captures) -> Option<String> {
-    if let Some(desc) = udt_str.name("udt_title") {
-        Some(String::from(desc.as_str()))
-    } else {
-        None
-    }
+/// Collect the attributes of a member into its metadata
+fn get_metadata(member: &MemberDecl) -> BTreeMap<String, String> {
+    member
+        .attributes
+        .iter()
+        .map(|attribute| (attribute.name.clone(), attribute.value.clone()))
+        .collect()
 }
 
-/// Get array bounds (if they exist) from the regex parser
-pub fn get_bounds(member_str: &Captures) -> Option<(isize, isize)> {
-    let (lower_bound, upper_bound) = (
-        if let Some(bound) = member_str.name("bound_lower") {
-            Some(
-                String::from(bound.as_str())
-                    .parse()
-                    .expect("Lower bound invalid format"),
-            )
-        } else {
-            None
-        },
-        if let Some(bound) = member_str.name("bound_upper") {
-            Some(
-                String::from(bound.as_str())
-                    .parse()
-                    .expect("Upper bound invalid format"),
-            )
-        } else {
-            None
-        },
-    );
+/// Longest name Logix allows for a data type or member
+pub const MAX_NAME_LENGTH: usize = 40;
 
-    if let (Some(lower), Some(upper)) = (lower_bound, upper_bound) {
-        Some((lower, upper))
-    } else {
-        None
+/// Name a hidden member holding packed BOOLs after the naming template,
+/// shortening the UDT name to keep the name within the length limit of Logix
+fn hidden_name(udt_name: &str, num: usize, config: &Config) -> String {
+    let mut template = config.naming.hidden_name.clone();
+    // The number keeps the hidden members of a UDT apart
+    if !template.contains("{n}") {
+        template.push_str("{n}");
     }
-}
 
-/// Get description (if it exists) from the regex parser
-pub fn get_member_description(member_str: &Captures) -> Option<String> {
-    if let Some(desc) = member_str.name("member_description") {
-        Some(String::from(desc.as_str()))
-    } else {
-        None
+    let render = |udt_name: &str| {
+        template
+            .replace("{prefix}", &config.naming.hidden_prefix)
+            .replace("{udt}", udt_name)
+            .replace("{n}", &num.to_string())
+    };
+
+    let name = render(udt_name);
+    let excess = name.chars().count().saturating_sub(MAX_NAME_LENGTH);
+    if excess == 0 {
+        return name;
     }
+
+    let kept = udt_name.chars().count().saturating_sub(excess);
+    let name = render(&udt_name.chars().take(kept).collect::<String>());
+
+    // Only when the rest of the template is too long by itself, keeping the number at the end
+    let excess = name.chars().count().saturating_sub(MAX_NAME_LENGTH);
+    name.chars().skip(excess).collect()
 }
 
-/// Determine if member is externally writeable
-pub fn external_write(member_str: &Captures) -> bool {
-    if let Some(ext_wrt) = member_str.name("ext_wrt") {
-        if ext_wrt.as_str().to_lowercase() == "false" {
-            false
-        } else {
-            true
+/// Rename the hidden members whose name is taken by another member of the UDT,
+/// along with the BOOLs packed into them
+fn unique_hidden_names(udt: &mut Udt) {
+    for index in 0..udt.members.len() {
+        if !udt.members[index].hidden {
+            continue;
         }
-    } else {
-        true
-    }
-}
 
-/// Determine if member is externally readable
-pub fn external_read(member_str: &Captures) -> bool {
-    if let Some(ext_vis) = member_str.name("ext_vis") {
-        if ext_vis.as_str().to_lowercase() == "false" {
-            false
-        } else {
-            true
+        let name = udt.members[index].name.clone();
+        let taken = |candidate: &str| {
+            udt.members.iter().enumerate().any(|(other, member)| {
+                other != index && member.name.eq_ignore_ascii_case(candidate)
+            })
+        };
+        if !taken(&name) {
+            continue;
         }
-    } else {
-        true
+
+        let unique = (1..)
+            .map(|num| {
+                let suffix = format!("_{}", num);
+                let kept = MAX_NAME_LENGTH - suffix.len();
+                format!("{}{}", name.chars().take(kept).collect::<String>(), suffix)
+            })
+            .find(|candidate| !taken(candidate))
+            .expect("Ran out of names for a hidden member");
+        log::debug!("{}: hidden member {} renamed to {}", udt.name, name, unique);
+
+        for member in &mut udt.members {
+            if member.target.as_ref() == Some(&name) {
+                member.target = Some(unique.clone());
+            }
+        }
+        udt.members[index].name = unique;
     }
 }
 
-/// Special case for inidividual bools to assign them to bits of hidden SINTs.
-/// Also creates the hidden SINTs as needed and adds them to the UDT
+/// Special case for inidividual bools to assign them to bits of hidden members,
+/// typed after the BOOL packing strategy.
+/// Also creates the hidden members as needed and adds them to the UDT
 pub fn get_target(
-    member_str: &Captures,
-    udts: &mut Vec<Udt>,
+    data_type: &str,
+    bounds: Option<(isize, isize)>,
+    udts: &mut [Udt],
     target_nums: &BoolTargets,
+    config: &Config,
 ) -> Option<String> {
-    let data_type = convert_type(&member_str["member_type"]).to_uppercase();
-    let mut target_name = "ZZZZZZZZZZ".to_string();
-    target_name.push_str(&udts.last().unwrap().name);
+    let (container, _) = config.packing().container()?;
 
-    if let (true, None) = (data_type == "BOOL", get_bounds(member_str)) {
-        target_name.push_str(&target_nums.target_num.to_string());
+    if let (true, None) = (data_type.eq_ignore_ascii_case("BOOL"), bounds) {
+        let target_name = hidden_name(&udts.last().unwrap().name, target_nums.target_num, config);
 
         if target_nums.bit_num == 0 {
+            log::debug!(
+                "{}: synthesized hidden {} {} for BOOL members",
+                udts.last().unwrap().name,
+                container,
+                target_name
+            );
+            // Contiguous hidden members are placed with their BOOLs, the others first
+            let index = if config.packing() == BoolPacking::Contiguous {
+                udts.last().unwrap().members.len()
+            } else {
+                target_nums.target_num
+            };
             udts.last_mut().unwrap().members.insert(
-                target_nums.target_num,
+                index,
                 UdtMember {
                     name: target_name.clone(),
                     description: None,
-                    data_type: "SINT".to_string(),
+                    data_type: container.to_string(),
                     array_bounds: None,
-                    external_read: false,
-                    external_write: false,
+                    metadata: BTreeMap::new(),
                     hidden: true,
                     target: None,
                     bit_num: None,
+                    span: None,
+                    default: None,
+                    radix: None,
+                    access: None,
+                    source: None,
                 },
             )
         }
-    }
 
-    if data_type.to_uppercase() == "BOOL" {
         Some(target_name)
     } else {
         None
     }
 }
 
-fn get_members(member_str: Captures, udts: &mut Vec<Udt>, target_nums: &mut BoolTargets) {
-    let data_type: String = convert_type(&member_str["member_type"]).into();
-    let bounds = get_bounds(&member_str);
-    let target = get_target(&member_str, udts, &target_nums);
+/// Resolve the dimensions of an array member to its bounds.
+/// Arrays with several dimensions are flattened, since Logix UDT members have only one
+fn get_bounds(
+    member: &MemberDecl,
+    udt_name: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Option<(isize, isize)>, Error> {
+    let mut dimensions = vec![];
+
+    for (lower, upper) in &member.dimensions {
+        let mut resolve = |bound: &Bound| match bound {
+            Bound::Value(value) => Some(*value),
+            Bound::Constant(name) => {
+                let value = config.constant(name);
+                if value.is_none() {
+                    diagnostics.push(
+                        Diagnostic::error(
+                            "unknown-constant",
+                            format!(
+                                "{}.{}: array bound {} is not a known constant",
+                                udt_name, member.name, name
+                            ),
+                        )
+                        .with_span(member.bounds_span)
+                        .with_help(format!("define {} under [constants] in the config", name)),
+                    );
+                }
+                value
+            }
+        };
+
+        let (Some(lower), Some(upper)) = (resolve(lower), resolve(upper)) else {
+            return Err(Error::Parse(format!(
+                "UDT {} could not be converted",
+                udt_name
+            )));
+        };
+
+        if upper < lower {
+            diagnostics.push(
+                Diagnostic::error(
+                    "invalid-bounds",
+                    format!(
+                        "{}.{}: upper bound {} is less than lower bound {}",
+                        udt_name, member.name, upper, lower
+                    ),
+                )
+                .with_span(member.bounds_span),
+            );
+            return Err(Error::Parse(format!(
+                "UDT {} could not be converted",
+                udt_name
+            )));
+        }
+
+        dimensions.push((lower, upper));
+    }
+
+    match dimensions[..] {
+        [] => Ok(None),
+        [bounds] => Ok(Some(bounds)),
+        _ => {
+            let count: isize = dimensions
+                .iter()
+                .map(|(lower, upper)| upper - lower + 1)
+                .product();
+            diagnostics.push(
+                Diagnostic::warning(
+                    "array-flattened",
+                    format!(
+                        "{}.{}: {}-dimensional array is flattened to {} elements",
+                        udt_name,
+                        member.name,
+                        dimensions.len(),
+                        count
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help(
+                    "Logix UDT members only have one dimension, index the elements row by row",
+                ),
+            );
+            Ok(Some((0, count - 1)))
+        }
+    }
+}
+
+/// A TIA Portal type of 16-bit characters
+#[derive(Clone, Copy)]
+enum WideType {
+    Char,
+    /// WSTRING with its declared length, None if it has none
+    String(Option<usize>),
+}
+
+/// Get the wide character type of a type, None if it is neither WSTRING nor WCHAR
+fn wide_type(siemens_type: &str) -> Option<WideType> {
+    let upper = siemens_type.to_uppercase();
+
+    if upper == "WCHAR" {
+        Some(WideType::Char)
+    } else if upper == "WSTRING" {
+        Some(WideType::String(None))
+    } else {
+        upper
+            .strip_prefix("WSTRING[")
+            .and_then(|rest| rest.strip_suffix(']'))
+            .and_then(|length| length.parse().ok())
+            .map(|length| WideType::String(Some(length)))
+    }
+}
+
+/// Length of a Siemens STRING or WSTRING without an explicit length
+const DEFAULT_STRING_LENGTH: usize = 254;
+
+/// Get the radix of a CHAR converted to SINT, showing it as a character unless configured not to
+fn char_radix(siemens_type: &str, data_type: &str, config: &Config) -> Option<Radix> {
+    (siemens_type.eq_ignore_ascii_case("CHAR")
+        && data_type.eq_ignore_ascii_case("SINT")
+        && config.chars != Chars::Sint)
+        .then_some(Radix::Ascii)
+}
+
+/// Convert a STRING or WSTRING member without a length according to the config
+fn convert_default_string(
+    member: &MemberDecl,
+    udt_name: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<String, Error> {
+    match config.default_string {
+        DefaultString::Logix => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "string-length",
+                    format!(
+                        "{}.{}: a STRING of {} characters becomes the 82 character STRING of Logix",
+                        udt_name, member.name, DEFAULT_STRING_LENGTH
+                    ),
+                )
+                .with_span(Some(member.type_span))
+                .with_help("longer texts are truncated, use `--default-string sized` to keep every character"),
+            );
+            Ok("STRING".to_string())
+        }
+        DefaultString::Sized => Ok(format!("STRING_{}", config.default_string_length)),
+        DefaultString::Fail => {
+            diagnostics.push(
+                Diagnostic::error(
+                    "string-length",
+                    format!("{}.{}: STRING has no length", udt_name, member.name),
+                )
+                .with_span(Some(member.type_span))
+                .with_help("declare the length in TIA Portal, such as String[82]"),
+            );
+            Err(Error::Unsupported(format!(
+                "{}.{} is a STRING without a length",
+                udt_name, member.name
+            )))
+        }
+    }
+}
+
+/// Convert a WSTRING or WCHAR member according to the config. As INT arrays,
+/// a WSTRING keeps the two words holding its maximum and actual length in front
+fn convert_wide_type(
+    member: &MemberDecl,
+    wide_type: WideType,
+    bounds: &mut Option<(isize, isize)>,
+    udt_name: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<String, Error> {
+    match (config.wide_strings, wide_type) {
+        (WideStrings::String, wide_type) => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "wide-string",
+                    format!(
+                        "{}.{}: 16-bit characters are narrowed to the 8-bit characters of Logix",
+                        udt_name, member.name
+                    ),
+                )
+                .with_span(Some(member.type_span))
+                .with_help("use `--wide-strings int-array` to keep every character"),
+            );
+
+            match wide_type {
+                // Logix has no character type, a SINT holds one character of a STRING
+                WideType::Char => Ok("SINT".to_string()),
+                WideType::String(None) => {
+                    convert_default_string(member, udt_name, config, diagnostics)
+                }
+                WideType::String(Some(length)) => Ok(format!("STRING_{}", length)),
+            }
+        }
+        (WideStrings::IntArray, WideType::Char) => Ok("INT".to_string()),
+        (WideStrings::IntArray, WideType::String(_)) if bounds.is_some() => {
+            diagnostics.push(
+                Diagnostic::error(
+                    "unsupported",
+                    format!(
+                        "{}.{}: arrays of WSTRING cannot be converted to INT arrays",
+                        udt_name, member.name
+                    ),
+                )
+                .with_span(Some(member.type_span)),
+            );
+            Err(Error::Unsupported(format!(
+                "{}.{} is an array of WSTRING",
+                udt_name, member.name
+            )))
+        }
+        (WideStrings::IntArray, WideType::String(length)) => {
+            let length = length.unwrap_or(DEFAULT_STRING_LENGTH);
+            *bounds = Some((0, length as isize + 1));
+            Ok("INT".to_string())
+        }
+    }
+}
+
+/// Checks if data type points to other data, such as VARIANT, ANY or REF_TO Int,
+/// which Logix UDTs cannot hold
+fn pointer_type(siemens_type: &str) -> bool {
+    let upper = siemens_type.to_uppercase();
+    ["VARIANT", "ANY", "POINTER"].contains(&upper.as_str()) || upper.starts_with("REF_TO ")
+}
+
+/// Replace a pointer member with the placeholder type of the config,
+/// or fail if there is none
+fn convert_pointer_type(
+    member: &MemberDecl,
+    siemens_type: &str,
+    udt_name: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<String, Error> {
+    let message = format!(
+        "{}.{}: {} points to other data and has no equivalent in Logix",
+        udt_name, member.name, siemens_type
+    );
+
+    match &config.placeholder_type {
+        Some(placeholder) => {
+            diagnostics.push(
+                Diagnostic::warning("pointer-type", message)
+                    .with_span(Some(member.type_span))
+                    .with_help(format!("the member is replaced with {}", placeholder)),
+            );
+            Ok(placeholder.clone())
+        }
+        None => {
+            diagnostics.push(
+                Diagnostic::error("pointer-type", message)
+                    .with_span(Some(member.type_span))
+                    .with_help("use `--placeholder-type` to replace the member, such as with DINT"),
+            );
+            Err(Error::Unsupported(format!(
+                "{}.{} is a {}",
+                udt_name, member.name, siemens_type
+            )))
+        }
+    }
+}
+
+/// Apply the lower bound policy of the config to an array not starting at 0.
+/// Rebased arrays keep their bounds, which are shifted when writing the L5X
+fn lower_bound(
+    member: &MemberDecl,
+    lower: isize,
+    upper: isize,
+    udt_name: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(isize, isize), Error> {
+    match config.lower_bounds {
+        LowerBounds::Rebase => Ok((lower, upper)),
+        LowerBounds::Pad if lower > 0 => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "index-padded",
+                    format!(
+                        "{}.{}: array bounds {}..{} become 0..{} in Logix, elements 0..{} are unused",
+                        udt_name,
+                        member.name,
+                        lower,
+                        upper,
+                        upper,
+                        lower - 1
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help("use `--lower-bounds rebase` to keep the element count instead"),
+            );
+            Ok((0, upper))
+        }
+        LowerBounds::Pad | LowerBounds::Fail => {
+            diagnostics.push(
+                Diagnostic::error(
+                    "index-offset-unsupported",
+                    format!(
+                        "{}.{}: array bounds {}..{} do not start at 0 like Logix arrays",
+                        udt_name, member.name, lower, upper
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help("use `--lower-bounds rebase` to shift the indexes to start at 0"),
+            );
+            Err(Error::Unsupported(format!(
+                "{}.{} has a lower bound of {}",
+                udt_name, member.name, lower
+            )))
+        }
+    }
+}
+
+/// Convert an LREAL member to REAL, warning about the lost precision
+fn lreal_to_real(
+    member: &MemberDecl,
+    reason: &str,
+    udt_name: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> String {
+    diagnostics.push(
+        Diagnostic::warning(
+            "precision-loss",
+            format!(
+                "{}.{}: LREAL becomes REAL {}",
+                udt_name, member.name, reason
+            ),
+        )
+        .with_span(Some(member.type_span))
+        .with_help("REAL keeps about 7 significant digits instead of 15"),
+    );
+    "REAL".to_string()
+}
+
+/// First Logix version with unsigned and 64-bit integers and LREAL on every controller
+const NEW_TYPES_VERSION: u32 = 32;
+
+/// Warn about members using types added in Logix 32 when the L5X is for an older version,
+/// which Studio 5000 rejects. `--target-logix` replaces them instead
+pub fn check_revision_types(udts: &[Udt], config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    let major = config
+        .software_revision
+        .split('.')
+        .next()
+        .and_then(|major| major.parse::<u32>().ok());
+    if major.is_none_or(|major| major >= NEW_TYPES_VERSION) {
+        return;
+    }
+
+    for udt in udts {
+        for member in udt.members.iter().filter(|member| !member.hidden) {
+            let new_type = ["LINT", "ULINT", "LREAL", "LDT", "USINT", "UINT", "UDINT"]
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&member.data_type));
+            if !new_type {
+                continue;
+            }
+
+            diagnostics.push(
+                Diagnostic::warning(
+                    "revision-type",
+                    format!(
+                        "{}.{}: {} needs Logix {} or later, but the L5X is for revision {}",
+                        udt.name,
+                        member.name,
+                        member.data_type,
+                        NEW_TYPES_VERSION,
+                        config.software_revision
+                    ),
+                )
+                .with_span(member.span)
+                .with_file(udt.file.clone())
+                .with_help(format!(
+                    "use `--target-logix {}` to replace the type",
+                    major.unwrap_or_default()
+                )),
+            );
+        }
+    }
+}
+
+/// Warn when the L5X claims a schema revision the writer does not produce, as every element
+/// and data type is written the way schema 1.0 defines them
+pub fn check_schema_revision(config: &Config, diagnostics: &mut Vec<Diagnostic>) {
+    if l5x::SCHEMA_REVISIONS.contains(&config.schema_revision.as_str()) {
+        return;
+    }
+
+    diagnostics.push(
+        Diagnostic::warning(
+            "schema-revision",
+            format!(
+                "The L5X is written following schema revision {}, but claims revision {}",
+                l5x::SCHEMA_REVISIONS.join(", "),
+                config.schema_revision
+            ),
+        )
+        .with_help("Studio 5000 may reject elements or data types the claimed revision lacks"),
+    );
+}
+
+/// Get the width in bits and signedness of an integer or bit string type,
+/// counting bit strings as unsigned
+fn integer_type(name: &str) -> Option<(usize, bool)> {
+    Some(match name.to_uppercase().as_str() {
+        "SINT" => (8, true),
+        "INT" => (16, true),
+        "DINT" => (32, true),
+        "LINT" => (64, true),
+        "USINT" | "BYTE" => (8, false),
+        "UINT" | "WORD" => (16, false),
+        "UDINT" | "DWORD" => (32, false),
+        "ULINT" | "LWORD" => (64, false),
+        _ => return None,
+    })
+}
+
+/// Get what a date or time type holds once converted to a plain integer
+fn time_unit(name: &str) -> Option<&'static str> {
+    Some(match name.to_uppercase().as_str() {
+        "TIME" | "S5TIME" => "a duration in milliseconds",
+        "TOD" | "TIME_OF_DAY" => "milliseconds since midnight",
+        "LTIME" => "a duration in nanoseconds",
+        "LTOD" | "LTIME_OF_DAY" => "nanoseconds since midnight",
+        "DATE" => "days since 1990-01-01",
+        "DT" | "DATE_AND_TIME" => "the BCD bytes of the date and time",
+        _ => return None,
+    })
+}
+
+/// Warn when a member keeps its value but not its meaning in Logix: bit strings becoming
+/// integers, dates and times becoming plain numbers, CHARs becoming integers, and integers
+/// changing their signedness or width
+fn semantics_warning(
+    member: &MemberDecl,
+    siemens_type: &str,
+    data_type: &str,
+    udt_name: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let bit_string = ["BYTE", "WORD", "DWORD", "LWORD"]
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(siemens_type));
+    let source = integer_type(siemens_type);
+    let target = integer_type(data_type);
+
+    let (code, message, help) = if let Some(unit) = time_unit(siemens_type) {
+        if target.is_none() {
+            return;
+        }
+        (
+            "time-as-integer",
+            format!("{} becomes {} holding {}", siemens_type, data_type, unit),
+            "Logix has no time types, convert the value in the receiving program",
+        )
+    } else if siemens_type.eq_ignore_ascii_case("CHAR") {
+        if custom_string_type(data_type) {
+            (
+                "char-array-string",
+                format!("array of CHAR becomes {}", data_type),
+                "the characters are in DATA and the receiving program must keep LEN up to date",
+            )
+        } else {
+            (
+                "char-as-integer",
+                format!("CHAR becomes {} holding the character code", data_type),
+                "the receiving program sees a number instead of a character",
+            )
+        }
+    } else if let (true, Some((_, signed))) = (bit_string, target) {
+        let message = if signed {
+            format!(
+                "bit string {} becomes the signed {}, values with the top bit set are negative",
+                siemens_type, data_type
+            )
+        } else {
+            format!(
+                "bit string {} becomes the integer {}",
+                siemens_type, data_type
+            )
+        };
+        (
+            "bit-string",
+            message,
+            "bits of the integer are still accessed as Member.N, but arithmetic and comparisons treat it as a number",
+        )
+    } else if let (Some((source_bits, source_signed)), Some((target_bits, target_signed))) =
+        (source, target)
+    {
+        if source_bits != target_bits {
+            (
+                "width-change",
+                format!(
+                    "{} ({} bits) becomes {} ({} bits)",
+                    siemens_type, source_bits, data_type, target_bits
+                ),
+                "check the range of the values exchanged through the member",
+            )
+        } else if source_signed != target_signed {
+            (
+                "signedness-change",
+                format!(
+                    "{} becomes {}, which changes the signedness",
+                    siemens_type, data_type
+                ),
+                "values outside the range of both types read differently",
+            )
+        } else {
+            return;
+        }
+    } else {
+        return;
+    };
+
+    diagnostics.push(
+        Diagnostic::warning(code, format!("{}.{}: {}", udt_name, member.name, message))
+            .with_span(Some(member.type_span))
+            .with_help(format!(
+                "{}, use `--allow {}` to hide this warning",
+                help, code
+            )),
+    );
+}
+
+/// Replace a type missing from Logix versions before 32. 64-bit integers and LDT become a pair
+/// of DINTs holding the low and high 32 bits, LREAL becomes REAL and the other unsigned
+/// integers become the signed integer of the same size.
+/// Returns the data type, bounds and description
+fn downgrade_type(
+    member: &MemberDecl,
+    data_type: String,
+    bounds: Option<(isize, isize)>,
+    description: Option<String>,
+    udt_name: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> (String, Option<(isize, isize)>, Option<String>) {
+    match data_type.to_uppercase().as_str() {
+        "LINT" | "ULINT" | "LDT" => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "long-type",
+                    format!(
+                        "{}.{}: {} is not supported by the target Logix version and becomes a pair of DINTs",
+                        udt_name, member.name, data_type
+                    ),
+                )
+                .with_span(Some(member.type_span))
+                .with_help("element 0 holds the low 32 bits and element 1 the high 32 bits"),
+            );
+
+            let bounds = match bounds {
+                Some((lower, upper)) => (lower, lower + 2 * (upper - lower + 1) - 1),
+                None => (0, 1),
+            };
+            let note = format!("{} as DINT pairs of the low and high 32 bits", data_type);
+            (
+                "DINT".to_string(),
+                Some(bounds),
+                annotate(description, note),
+            )
+        }
+        "LREAL" => {
+            let reason = "since the target Logix version has no LREAL";
+            let data_type = lreal_to_real(member, reason, udt_name, diagnostics);
+            (data_type, bounds, description)
+        }
+        "USINT" | "UINT" | "UDINT" => {
+            let signed = data_type[1..].to_string();
+            diagnostics.push(
+                Diagnostic::warning(
+                    "unsigned-type",
+                    format!(
+                        "{}.{}: {} is not supported by the target Logix version and becomes {}",
+                        udt_name, member.name, data_type, signed
+                    ),
+                )
+                .with_span(Some(member.type_span))
+                .with_help("values above the largest signed value become negative"),
+            );
+            (signed, bounds, description)
+        }
+        _ => (data_type, bounds, description),
+    }
+}
+
+/// Add a note to the description of a member, in parentheses after the comment if there is one
+fn annotate(description: Option<String>, note: String) -> Option<String> {
+    Some(match description {
+        Some(description) => format!("{} ({})", description, note),
+        None => note,
+    })
+}
+
+/// Convert a BOOL array whose element count is not a multiple of 32, the only sizes
+/// Studio 5000 allows, after the BOOL array policy. Returns the data type, bounds and description
+fn convert_bool_array(
+    member: &MemberDecl,
+    lower: isize,
+    upper: isize,
+    description: Option<String>,
+    udt_name: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> (String, Option<(isize, isize)>, Option<String>) {
+    let count = (upper - lower + 1) as usize;
+    let rounded = count.div_ceil(32) * 32;
+
+    match config.bool_arrays {
+        BoolArrays::Round => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "bool-array-rounded",
+                    format!(
+                        "{}.{}: BOOL array of {} elements is rounded up to {}",
+                        udt_name, member.name, count, rounded
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help("use a multiple of 32 elements to keep the layout of the UDT"),
+            );
+            let bounds = (lower, lower + rounded as isize - 1);
+            ("BOOL".to_string(), Some(bounds), description)
+        }
+        BoolArrays::Keep => {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "bool-array-size",
+                    format!(
+                        "{}.{}: BOOL array of {} elements is kept, Studio 5000 rounds it up to {} on import",
+                        udt_name, member.name, count, rounded
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help("use a multiple of 32 elements to keep the layout of the UDT"),
+            );
+            ("BOOL".to_string(), Some((lower, upper)), description)
+        }
+        BoolArrays::Dint => {
+            let elements = count.div_ceil(32);
+            diagnostics.push(
+                Diagnostic::warning(
+                    "bool-array-dint",
+                    format!(
+                        "{}.{}: BOOL array of {} elements is converted to a DINT array of {}",
+                        udt_name, member.name, count, elements
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help(
+                    "BOOL i is bit i mod 32 of DINT i / 32, the member description notes it",
+                ),
+            );
+
+            let note = format!(
+                "Array[0..{}] of BOOL, BOOL i is bit i mod 32 of element i / 32",
+                count - 1
+            );
+            let description = annotate(description, note);
+            let bounds = (lower, lower + elements as isize - 1);
+            ("DINT".to_string(), Some(bounds), description)
+        }
+    }
+}
+
+fn get_members(
+    member: &MemberDecl,
+    siemens_type: &str,
+    udts: &mut [Udt],
+    target_nums: &mut BoolTargets,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), Error> {
+    let udt_name = udts.last().expect("No UDTs found!").name.clone();
+    let mut bounds = get_bounds(member, &udt_name, config, diagnostics)?;
+    let declared = bounds.map(|(lower, upper)| (upper - lower + 1) as usize);
+    if let Some((lower, upper)) = bounds.filter(|(lower, _)| *lower != 0) {
+        bounds = Some(lower_bound(
+            member,
+            lower,
+            upper,
+            &udt_name,
+            config,
+            diagnostics,
+        )?);
+    }
+    let member_override = config.member_override(&udt_name, &member.name);
+    let forced_type = member_override.and_then(|settings| settings.data_type.as_ref());
+    let data_type = match (forced_type, wide_type(siemens_type)) {
+        (Some(data_type), _) => data_type.clone(),
+        _ if pointer_type(siemens_type) && config.type_override(siemens_type).is_none() => {
+            convert_pointer_type(member, siemens_type, &udt_name, config, diagnostics)?
+        }
+        (None, Some(wide_type)) if config.type_override(siemens_type).is_none() => {
+            convert_wide_type(
+                member,
+                wide_type,
+                &mut bounds,
+                &udt_name,
+                config,
+                diagnostics,
+            )?
+        }
+        _ if siemens_type.eq_ignore_ascii_case("CHAR")
+            && config.chars == Chars::String
+            && config.type_override(siemens_type).is_none()
+            && bounds.is_some() =>
+        {
+            let count = declared.expect("Arrays have a declared count");
+            bounds = None;
+            format!("STRING_{}", count)
+        }
+        _ if siemens_type.eq_ignore_ascii_case("STRING")
+            && config.type_override(siemens_type).is_none() =>
+        {
+            convert_default_string(member, &udt_name, config, diagnostics)?
+        }
+        _ => convert_type(siemens_type, config),
+    };
+    let target = get_target(&data_type, bounds, udts, target_nums, config);
+
+    log::trace!(
+        "{}.{}: {} converted to {}",
+        udts.last().expect("No UDTs found!").name,
+        member.name,
+        siemens_type,
+        data_type
+    );
+    semantics_warning(member, siemens_type, &data_type, &udt_name, diagnostics);
+
+    if let Some((lower, upper)) = bounds {
+        let count = (upper - lower + 1) as usize;
+
+        if lower != 0 {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "index-offset",
+                    format!(
+                        "{}.{}: array bounds {}..{} become 0..{} in Logix",
+                        udts.last().expect("No UDTs found!").name,
+                        member.name,
+                        lower,
+                        upper,
+                        count - 1
+                    ),
+                )
+                .with_span(member.bounds_span)
+                .with_help(format!(
+                    "index {} in TIA Portal is index 0 in Logix, the member description notes the original bounds",
+                    lower
+                )),
+            );
+        }
+    }
+
+    // Keep the original bounds of shifted arrays visible in the project
+    let description = match bounds {
+        Some((lower, upper)) if lower != 0 => annotate(
+            member.comment.clone(),
+            format!("TIA Portal indexes {}..{}", lower, upper),
+        ),
+        _ => member.comment.clone(),
+    };
+
+    let (data_type, bounds, description) = match bounds {
+        Some((lower, upper))
+            if data_type.eq_ignore_ascii_case("BOOL")
+                && !((upper - lower + 1) as usize).is_multiple_of(32) =>
+        {
+            convert_bool_array(
+                member,
+                lower,
+                upper,
+                description,
+                &udt_name,
+                config,
+                diagnostics,
+            )
+        }
+        _ => (data_type, bounds, description),
+    };
+
+    let new_types = config
+        .target_logix
+        .is_none_or(|version| version >= NEW_TYPES_VERSION);
+    let (data_type, bounds, description) = if forced_type.is_some() {
+        (data_type, bounds, description)
+    } else if !new_types {
+        downgrade_type(
+            member,
+            data_type,
+            bounds,
+            description,
+            &udt_name,
+            diagnostics,
+        )
+    } else if config.lreal_as_real && data_type.eq_ignore_ascii_case("LREAL") {
+        let reason = "as configured";
+        let data_type = lreal_to_real(member, reason, &udt_name, diagnostics);
+        (data_type, bounds, description)
+    } else {
+        (data_type, bounds, description)
+    };
+
+    let description = if config.original_types && !siemens_type.eq_ignore_ascii_case(&data_type) {
+        annotate(description, format!("TIA Portal type {}", siemens_type))
+    } else {
+        description
+    };
+
+    let udt_member = UdtMember {
+        name: member.name.clone(),
+        description,
+        data_type: data_type.clone(),
+        array_bounds: bounds,
+        metadata: get_metadata(member),
+        hidden: false,
+        target: target.clone(),
+        bit_num: if target.is_some() {
+            Some(target_nums.bit_num)
+        } else {
+            None
+        },
+        span: Some(member.type_span),
+        default: member.initial.clone(),
+        radix: member_override
+            .and_then(|settings| settings.radix)
+            .or_else(|| {
+                config.radix_override(&[
+                    &format!("{}.{}", udt_name, member.name),
+                    siemens_type,
+                    &data_type,
+                ])
+            })
+            .or_else(|| char_radix(siemens_type, &data_type, config)),
+        access: member_override.and_then(|settings| settings.external_access),
+        source: Some(SourceType {
+            name: siemens_type.to_string(),
+            count: declared,
+        }),
+    };
+
+    if let Some(default) = &udt_member.default {
+        log::trace!(
+            "{}.{}: initial value {}",
+            udt_name,
+            member.name,
+            default.join(", ")
+        );
+
+        // Start values end up in the data of the DB tags
+        let unconverted = default
+            .iter()
+            .filter(|value| !value.is_empty())
+            .find(|value| data::logix_value(value, siemens_type, &data_type).is_none());
+        if let Some(value) = unconverted.filter(|_| config.db_tags) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "initial-value",
+                    format!(
+                        "{}.{}: initial value {} cannot be converted to {}, the tag starts at 0",
+                        udt_name, member.name, value, data_type
+                    ),
+                )
+                .with_span(Some(member.type_span)),
+            );
+        }
+
+        let count = udt_member.element_count().unwrap_or(1);
+        if default.len() > count {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "initial-value-count",
+                    format!(
+                        "{}.{}: {} initial values given for {} element(s), the rest are ignored",
+                        udt_name,
+                        member.name,
+                        default.len(),
+                        count
+                    ),
+                )
+                .with_span(Some(member.type_span)),
+            );
+        }
+    }
 
     udts.last_mut()
         .expect("No UDTs found!")
         .members
-        .push(UdtMember {
-            name: member_str["member_name"].into(),
-            description: get_member_description(&member_str),
-            data_type: data_type.clone(),
-            array_bounds: bounds.clone(),
-            external_write: external_write(&member_str),
-            external_read: external_read(&member_str),
+        .push(udt_member);
+
+    if target.is_some() {
+        target_nums.inc();
+    } else if config.packing() == BoolPacking::Contiguous {
+        target_nums.next();
+    }
+    Ok(())
+}
+
+/// Get the UDTs used as the data type of members of the given UDT,
+/// or as the type of the elements of array members
+pub fn direct_dependencies<'a>(udt: &Udt, udts: &'a [Udt]) -> Vec<&'a Udt> {
+    udts.iter()
+        .filter(|other| {
+            udt.members
+                .iter()
+                .any(|member| member.data_type.eq_ignore_ascii_case(&other.name))
+        })
+        .collect()
+}
+
+/// Get the UDTs the given UDT uses directly or through the members of other UDTs,
+/// in the order of the list
+pub fn all_dependencies<'a>(udt: &Udt, udts: &'a [Udt]) -> Vec<&'a Udt> {
+    let mut found = direct_dependencies(udt, udts);
+    let mut index = 0;
+
+    while index < found.len() {
+        for dependency in direct_dependencies(found[index], udts) {
+            if !found
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&dependency.name))
+            {
+                found.push(dependency);
+            }
+        }
+        index += 1;
+    }
+
+    udts.iter()
+        .filter(|other| !other.name.eq_ignore_ascii_case(&udt.name))
+        .filter(|other| {
+            found
+                .iter()
+                .any(|dependency| dependency.name.eq_ignore_ascii_case(&other.name))
+        })
+        .collect()
+}
+
+/// Spell every reference to a UDT or excluded UDT like its name. Logix names ignore case,
+/// but Siemens sources often mix spellings such as `motorData` and `MotorData`.
+/// UDTs whose names only differ in case are reported, as Studio 5000 rejects the second one
+pub fn canonical_references(
+    udts: &mut [Udt],
+    excluded: &[String],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let names: Vec<String> = udts
+        .iter()
+        .map(|udt| udt.name.clone())
+        .chain(excluded.iter().cloned())
+        .collect();
+
+    for (index, udt) in udts.iter().enumerate() {
+        if let Some(other) = names[..index]
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(&udt.name))
+        {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "duplicate-type",
+                    format!(
+                        "UDT {} has the same name as UDT {} in Logix",
+                        udt.name, other
+                    ),
+                )
+                .with_span(udt.span)
+                .with_file(udt.file.clone())
+                .with_help("Logix names ignore case, rename one of the UDTs"),
+            );
+        }
+    }
+
+    for member in udts.iter_mut().flat_map(|udt| &mut udt.members) {
+        if let Some(name) = names
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(&member.data_type))
+        {
+            member.data_type = name.clone();
+        }
+    }
+}
+
+/// Get the names of the user data types referenced by the members of a UDT, without duplicates.
+/// Array members reference the type of their elements
+pub fn referenced_types(udt: &Udt) -> Vec<&str> {
+    let mut types: Vec<&str> = vec![];
+
+    for member in &udt.members {
+        if !member.hidden
+            && !elementary_type(&member.data_type)
+            && !types
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&member.data_type))
+        {
+            types.push(&member.data_type);
+        }
+    }
+
+    types
+}
+
+/// Add a string data type for every custom length string used by the UDTs and not defined
+/// by them, in front of them. Logix strings are a DINT length and a SINT array of characters
+pub fn add_string_types(udts: Vec<Udt>) -> Vec<Udt> {
+    let mut strings: Vec<Udt> = vec![];
+
+    for member in udts.iter().flat_map(|udt| &udt.members) {
+        let Some(length) = member
+            .data_type
+            .get("STRING_".len()..)
+            .filter(|_| custom_string_type(&member.data_type))
+            .and_then(|length| length.parse::<isize>().ok())
+        else {
+            continue;
+        };
+        let defined = udts
+            .iter()
+            .chain(&strings)
+            .any(|udt| udt.name.eq_ignore_ascii_case(&member.data_type));
+        if defined {
+            continue;
+        }
+
+        log::debug!("Generated string data type {}", member.data_type);
+        let string_member = |name: &str, data_type: &str, array_bounds| UdtMember {
+            name: name.to_string(),
+            description: None,
+            data_type: data_type.to_string(),
+            array_bounds,
+            metadata: BTreeMap::new(),
             hidden: false,
-            target: target.clone(),
-            bit_num: if data_type.to_uppercase() == "BOOL" && bounds == None {
-                Some(target_nums.bit_num)
-            } else {
-                None
-            },
+            target: None,
+            bit_num: None,
+            span: None,
+            default: None,
+            radix: None,
+            access: None,
+            source: None,
+        };
+        strings.push(Udt {
+            name: member.data_type.clone(),
+            description: None,
+            version: String::new(),
+            members: vec![
+                string_member("LEN", "DINT", None),
+                string_member("DATA", "SINT", Some((0, length - 1))),
+            ],
+            span: None,
+            file: None,
+            data_block: false,
+            string: true,
+            section: None,
         });
+    }
 
-    if let &Some(_) = &target {
-        target_nums.inc();
+    strings.extend(udts);
+    strings
+}
+
+/// Add the UDTs of the library referenced by the given UDTs, directly or through other
+/// library UDTs, in front of them. UDTs defined by both keep the given definition
+pub fn add_library(udts: Vec<Udt>, library: &[Udt]) -> Vec<Udt> {
+    let mut used: Vec<&Udt> = udts.iter().collect();
+    let mut added: Vec<&Udt> = vec![];
+
+    while let Some(udt) = library.iter().find(|udt| {
+        !used
+            .iter()
+            .any(|user| user.name.eq_ignore_ascii_case(&udt.name))
+            && used.iter().any(|user| {
+                referenced_types(user)
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&udt.name))
+            })
+    }) {
+        log::debug!("Added {} from the library", udt.name);
+        used.push(udt);
+        added.push(udt);
+    }
+
+    added.into_iter().cloned().chain(udts).collect()
+}
+
+/// Order the UDTs so every UDT comes after the UDTs it references,
+/// otherwise keeping their order
+pub fn sort_udts(udts: Vec<Udt>) -> Vec<Udt> {
+    fn visit(index: usize, udts: &[Udt], visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[index] {
+            return;
+        }
+        // Marked before visiting the references, so circular references can't recurse forever
+        visited[index] = true;
+
+        for name in referenced_types(&udts[index]) {
+            if let Some(reference) = udts
+                .iter()
+                .position(|udt| udt.name.eq_ignore_ascii_case(name))
+            {
+                visit(reference, udts, visited, order);
+            }
+        }
+        order.push(index);
+    }
+
+    let mut visited = vec![false; udts.len()];
+    let mut order = vec![];
+    for index in 0..udts.len() {
+        visit(index, &udts, &mut visited, &mut order);
+    }
+
+    let mut udts: Vec<Option<Udt>> = udts.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|index| udts[index].take())
+        .collect()
+}
+
+/// Compile wildcard patterns for UDT names
+fn build_patterns(patterns: &[String]) -> Result<Vec<Pattern>, Error> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .map_err(|err| Error::Usage(format!("Invalid pattern {}: {}", pattern, err)))
+        })
+        .collect()
+}
+
+/// Check if a UDT name matches any of the wildcard patterns, ignoring case
+fn matches_any(name: &str, patterns: &[Pattern]) -> bool {
+    let options = MatchOptions {
+        case_sensitive: false,
+        ..MatchOptions::new()
+    };
+
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches_with(name, options))
+}
+
+/// Remove the UDTs not selected by the `only` and `exclude` patterns of the config.
+/// Returns the remaining UDTs and the names of the removed ones
+pub fn filter_udts(udts: Vec<Udt>, config: &Config) -> Result<(Vec<Udt>, Vec<String>), Error> {
+    let only = build_patterns(&config.only)?;
+    let exclude = build_patterns(&config.exclude)?;
+    let mut excluded = vec![];
+
+    let udts = udts
+        .into_iter()
+        .filter(|udt| {
+            let keep = (only.is_empty() || matches_any(&udt.name, &only))
+                && !matches_any(&udt.name, &exclude);
+
+            if !keep {
+                log::debug!("Excluded UDT {}", udt.name);
+                excluded.push(udt.name.clone());
+            }
+            keep
+        })
+        .collect();
+
+    Ok((udts, excluded))
+}
+
+/// Report every UDT without a VERSION as an error, used by `--require-version`
+pub fn check_versions(udts: &[Udt], diagnostics: &mut Vec<Diagnostic>) -> Result<(), Error> {
+    let mut missing = 0;
+
+    for udt in udts.iter().filter(|udt| udt.version.is_empty()) {
+        diagnostics.push(
+            Diagnostic::error(
+                "missing-version",
+                format!("{}: UDT has no VERSION", udt.name),
+            )
+            .with_span(udt.span)
+            .with_file(udt.file.clone()),
+        );
+        missing += 1;
+    }
+
+    if missing > 0 {
+        return Err(Error::Check(format!("{} UDT(s) have no VERSION", missing)));
+    }
+    Ok(())
+}
+
+/// Warn about members whose data type is neither elementary, one of the given UDTs
+/// nor mapped to in the config, including members referencing UDTs that were excluded
+/// from the conversion. Undefined types fail the conversion or get a stub UDT
+/// in front of the UDTs if the config asks for it
+pub fn check_types(
+    udts: Vec<Udt>,
+    excluded: &[String],
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<Udt>, Error> {
+    let mut undefined: Vec<String> = vec![];
+
+    for udt in &udts {
+        for member in &udt.members {
+            // Types mapped to in the config are known to exist in the project
+            let defined = elementary_type(&member.data_type)
+                || udts
+                    .iter()
+                    .any(|other| other.name.eq_ignore_ascii_case(&member.data_type))
+                || config
+                    .types
+                    .values()
+                    .chain(
+                        config
+                            .members
+                            .values()
+                            .filter_map(|settings| settings.data_type.as_ref()),
+                    )
+                    .any(|mapped| mapped.eq_ignore_ascii_case(&member.data_type));
+
+            if excluded
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&member.data_type))
+            {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "excluded-type",
+                        format!(
+                            "{}.{}: data type {} is excluded but still referenced, it must already exist in the project",
+                            udt.name, member.name, member.data_type
+                        ),
+                    )
+                    .with_span(member.span)
+                    .with_file(udt.file.clone())
+                    .with_help(format!("remove the filter excluding {}", member.data_type)),
+                );
+            } else if !defined {
+                let message = match config.unknown_types {
+                    UnknownTypes::Pass => "must already exist in the project",
+                    UnknownTypes::Fail => "cannot be converted",
+                    UnknownTypes::Stub => "is replaced by a stub UDT",
+                };
+                let message = format!(
+                    "{}.{}: data type {} is not defined in the input and {}",
+                    udt.name, member.name, member.data_type, message
+                );
+                let diagnostic = if config.unknown_types == UnknownTypes::Fail {
+                    Diagnostic::error("undefined-type", message)
+                } else {
+                    Diagnostic::warning("undefined-type", message)
+                };
+                diagnostics.push(
+                    diagnostic
+                        .with_span(member.span)
+                        .with_file(udt.file.clone())
+                        .with_help(format!(
+                            "add the UDT {} to the input, or map it to an existing type in the config",
+                            member.data_type
+                        )),
+                );
+
+                if !undefined
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(&member.data_type))
+                {
+                    undefined.push(member.data_type.clone());
+                }
+            }
+        }
+    }
+
+    match config.unknown_types {
+        UnknownTypes::Fail if !undefined.is_empty() => Err(Error::Unsupported(format!(
+            "Undefined data types: {}",
+            undefined.join(", ")
+        ))),
+        UnknownTypes::Stub => {
+            let mut stubs = vec![];
+            for name in undefined {
+                log::debug!("Generated stub UDT {} for an undefined type", name);
+                let source = format!(
+                    "TYPE \"{}\"\nTITLE = Stub for a type not defined in the input\nSTRUCT Stub : DInt; END_STRUCT;\nEND_TYPE",
+                    name
+                );
+                stubs.append(&mut get_udts(&source, config, &mut vec![])?);
+            }
+            stubs.extend(udts);
+            Ok(stubs)
+        }
+        _ => Ok(udts),
+    }
+}
+
+/// Convert the members of a STRUCT into the UDT, preceded by the UDTs synthesized
+/// for the nested STRUCTs among its members
+fn build_udt(
+    udt: Udt,
+    members: &[MemberDecl],
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<Udt>, Error> {
+    let mut nested = vec![];
+    let mut udts = vec![udt];
+    let bits = config.packing().container().map_or(0, |(_, bits)| bits);
+    let mut target_nums = BoolTargets::new(bits);
+    // The first error, returned once every member has been checked for more
+    let mut failure = None;
+
+    for member in members {
+        let siemens_type = match &member.data_type {
+            DataType::Named(siemens_type) => siemens_type.clone(),
+            DataType::Struct(struct_members) => {
+                let parent = &udts[0];
+                let name = config
+                    .naming
+                    .struct_name
+                    .replace("{udt}", &parent.name)
+                    .replace("{member}", &member.name);
+                log::debug!(
+                    "{}.{}: synthesized UDT {} for nested STRUCT",
+                    parent.name,
+                    member.name,
+                    name
+                );
+
+                let child = Udt {
+                    name: name.clone(),
+                    description: member.comment.clone(),
+                    version: parent.version.clone(),
+                    members: vec![],
+                    span: Some(member.type_span),
+                    file: None,
+                    data_block: false,
+                    string: false,
+                    section: None,
+                };
+                match build_udt(child, struct_members, config, diagnostics) {
+                    Ok(mut child) => nested.append(&mut child),
+                    Err(err) => {
+                        failure.get_or_insert(err);
+                    }
+                }
+                name
+            }
+        };
+
+        if let Err(err) = get_members(
+            member,
+            &siemens_type,
+            &mut udts,
+            &mut target_nums,
+            config,
+            diagnostics,
+        ) {
+            failure.get_or_insert(err);
+        }
+    }
+
+    if let Some(err) = failure {
+        return Err(err);
+    }
+
+    unique_hidden_names(&mut udts[0]);
+
+    let udt = &udts[0];
+    log::debug!("Found UDT {} with {} members", udt.name, udt.members.len());
+
+    // Studio 5000 rejects data types without members
+    if udt.members.is_empty() {
+        diagnostics.push(
+            Diagnostic::warning("empty-udt", format!("{}: UDT has no members", udt.name))
+                .with_span(udt.span),
+        );
+    }
+
+    nested.append(&mut udts);
+    Ok(nested)
+}
+
+/// Sections of an FB or FC interface converted to UDTs, with their names in the UDT names.
+/// Temporary and constant sections are not part of the data of an instance
+const INTERFACE_SECTIONS: [(SectionKind, &str); 4] = [
+    (SectionKind::Input, "Input"),
+    (SectionKind::Output, "Output"),
+    (SectionKind::InOut, "InOut"),
+    (SectionKind::Static, "Static"),
+];
+
+/// Temporary members are local tags of an Add-On Instruction, along with the static ones
+const AOI_SECTIONS: [(SectionKind, &str); 5] = [
+    (SectionKind::Input, "Input"),
+    (SectionKind::Output, "Output"),
+    (SectionKind::InOut, "InOut"),
+    (SectionKind::Static, "Static"),
+    (SectionKind::Temp, "Temp"),
+];
+
+/// Split the interface of an FB or FC into the name, members and section of each UDT
+/// to convert, either one per non-empty section or a single one named after the block
+fn split_interface(
+    decl: &TypeDecl,
+    config: &Config,
+) -> Vec<(String, Vec<MemberDecl>, Option<SectionKind>)> {
+    let kinds = if config.interface == InterfaceLayout::Aoi {
+        &AOI_SECTIONS[..]
+    } else {
+        &INTERFACE_SECTIONS[..]
+    };
+    let sections = kinds.iter().map(|(kind, section)| {
+        let members: Vec<MemberDecl> = decl
+            .sections
+            .iter()
+            .filter(|other| other.kind == *kind)
+            .flat_map(|other| other.members.iter().cloned())
+            .collect();
+        (*kind, section, members)
+    });
+
+    match config.interface {
+        InterfaceLayout::Merged => vec![(
+            decl.name.clone(),
+            sections.flat_map(|(_, _, members)| members).collect(),
+            None,
+        )],
+        InterfaceLayout::Sections | InterfaceLayout::Aoi => sections
+            .filter(|(_, _, members)| !members.is_empty())
+            .map(|(kind, section, members)| {
+                let name = config
+                    .naming
+                    .section_name
+                    .replace("{block}", &decl.name)
+                    .replace("{section}", section);
+                let kind = (config.interface == InterfaceLayout::Aoi).then_some(kind);
+                (name, members, kind)
+            })
+            .collect(),
+        InterfaceLayout::Skip => {
+            log::debug!("Skipped the interface of {}", decl.name);
+            vec![]
+        }
+    }
+}
+
+/// Parse the UDTs of a UDT, DB or block source or a SimaticML document exported from TIA Portal.
+/// Report a parse error and turn it into the error of the conversion
+fn parse_error(err: ParseError, diagnostics: &mut Vec<Diagnostic>) -> Error {
+    let (line, column) = (err.span.line, err.span.column);
+
+    if err.unsupported {
+        diagnostics.push(Diagnostic::error("unsupported", err.message).with_span(Some(err.span)));
+        Error::Unsupported(format!(
+            "The input could not be converted, unsupported construct at line {} column {}",
+            line, column
+        ))
+    } else {
+        diagnostics.push(Diagnostic::error("syntax", err.message).with_span(Some(err.span)));
+        Error::Parse(format!(
+            "The input could not be parsed, syntax error at line {} column {}",
+            line, column
+        ))
+    }
+}
+
+/// Get the index of an element in the initial values of an array member, counting from its
+/// first element with the last index changing fastest
+fn element_index(member: &MemberDecl, indices: &[isize], config: &Config) -> Result<usize, String> {
+    if indices.len() != member.dimensions.len() {
+        return Err(format!(
+            "{} has {} dimension(s), but {} index(es) are given",
+            member.name,
+            member.dimensions.len(),
+            indices.len()
+        ));
+    }
+
+    let mut position = 0;
+    for ((lower, upper), &index) in member.dimensions.iter().zip(indices) {
+        let resolve = |bound: &Bound| match bound {
+            Bound::Value(value) => Some(*value),
+            Bound::Constant(name) => config.constant(name),
+        };
+        let (Some(lower), Some(upper)) = (resolve(lower), resolve(upper)) else {
+            return Err(format!("the bounds of {} are not known", member.name));
+        };
+        if index < lower || index > upper {
+            return Err(format!(
+                "index {} is outside of {}..{}",
+                index, lower, upper
+            ));
+        }
+        position = position * (upper - lower + 1) + index - lower;
     }
+    Ok(position as usize)
 }
 
-pub fn get_udts(content: String) -> Vec<Udt> {
-    // Generate regex patterns before looping to avoid repeatedly compiling them
-    let udt_regex = build_udt_regex();
-    let member_regex = build_member_regex();
+/// Replace the initial value of the member or element a start value is assigned to.
+/// Members of UDT instances and elements of arrays of STRUCTs share their type with others
+/// and cannot have start values of their own
+fn assign(
+    members: &mut [MemberDecl],
+    path: &[(String, Vec<isize>)],
+    value: &str,
+    config: &Config,
+) -> Result<(), String> {
+    let ((name, indices), rest) = path.split_first().expect("Paths are not empty");
+    let member = members
+        .iter_mut()
+        .find(|member| member.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("there is no member {}", name))?;
+
+    if !rest.is_empty() {
+        return match &mut member.data_type {
+            DataType::Struct(members) if member.dimensions.is_empty() && indices.is_empty() => {
+                assign(members, rest, value, config)
+            }
+            DataType::Struct(_) => {
+                Err("the elements of an array of STRUCTs share their start values".to_string())
+            }
+            DataType::Named(data_type) => Err(format!(
+                "{} takes the start values of its type {}",
+                member.name, data_type
+            )),
+        };
+    }
+
+    let index = element_index(member, indices, config)?;
+    // Elements before the assigned one keep no start value, which leaves them at 0
+    let initial = member.initial.get_or_insert_with(Vec::new);
+    if initial.len() <= index {
+        initial.resize(index + 1, String::new());
+    }
+    initial[index] = value.to_string();
+    Ok(())
+}
+
+/// Get the members of a DB with the start values assigned after its `BEGIN`
+/// replacing their initial values
+fn assigned_members(
+    decl: &TypeDecl,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<MemberDecl> {
+    let mut members = decl.members.clone();
+
+    for assignment in &decl.assignments {
+        let Err(reason) = assign(&mut members, &assignment.path, &assignment.value, config) else {
+            continue;
+        };
+        if !config.db_tags {
+            continue;
+        }
+
+        let path: Vec<String> = assignment
+            .path
+            .iter()
+            .map(|(name, indices)| {
+                if indices.is_empty() {
+                    return name.clone();
+                }
+                let indices: Vec<String> = indices.iter().map(isize::to_string).collect();
+                format!("{}[{}]", name, indices.join(","))
+            })
+            .collect();
+        diagnostics.push(
+            Diagnostic::warning(
+                "start-value",
+                format!(
+                    "{}.{}: start value {} is ignored, {}",
+                    decl.name,
+                    path.join("."),
+                    assignment.value,
+                    reason
+                ),
+            )
+            .with_span(Some(assignment.span)),
+        );
+    }
+    members
+}
+
+/// Nested STRUCTs become UDTs of their own, placed before the UDT using them,
+/// global DBs become UDTs named after the DB and FB and FC interfaces are split
+/// according to the config
+pub fn get_udts(
+    content: &str,
+    config: &Config,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<Udt>, Error> {
+    let parsed = if simatic_ml::is_simatic_ml(content) {
+        simatic_ml::parse(content, config.comment_language.as_deref())
+    } else {
+        parser::parse(content)
+    };
+    let parsed = parsed.map_err(|err| parse_error(err, diagnostics))?;
     let mut udts: Vec<Udt> = vec![];
+    // The first error, returned once every UDT has been checked for more
+    let mut failure = None;
 
-    for udt_str in udt_regex.captures_iter(&content) {
-        udts.push(Udt {
-            name: udt_str["udt_type"].into(),
-            description: get_udt_description(&udt_str),
-            _version: udt_str["udt_version"].into(),
-            members: vec![],
-        });
+    for err in parsed.errors {
+        if config.strict {
+            failure.get_or_insert(parse_error(err, diagnostics));
+        } else {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "skipped",
+                    format!(
+                        "skipped a construct that could not be parsed, {}",
+                        err.message
+                    ),
+                )
+                .with_span(Some(err.span))
+                .with_help("use `--strict` to fail on it instead"),
+            );
+        }
+    }
+
+    for decl in parsed.types {
+        if let Some(instance_of) = &decl.instance_of {
+            diagnostics.push(
+                Diagnostic::warning(
+                    "instance-db",
+                    format!(
+                        "{}: DB is an instance of {} and has no structure of its own, skipping it",
+                        decl.name, instance_of
+                    ),
+                )
+                .with_span(Some(decl.name_span))
+                .with_help(format!("convert the export of {} instead", instance_of)),
+            );
+            continue;
+        }
 
-        //Parse members in UDT body
-        let mut target_nums = BoolTargets::new();
-        let body: String = udt_str["udt_body"].into();
+        let parts = match decl.block {
+            BlockKind::Type => vec![(decl.name.clone(), decl.members.clone(), None)],
+            BlockKind::DataBlock => {
+                vec![(
+                    decl.name.clone(),
+                    assigned_members(&decl, config, diagnostics),
+                    None,
+                )]
+            }
+            BlockKind::FunctionBlock | BlockKind::Function => split_interface(&decl, config),
+        };
 
-        for member_str in member_regex.captures_iter(&body) {
-            get_members(member_str, &mut udts, &mut target_nums);
+        for (name, members, section) in parts {
+            let udt = Udt {
+                name,
+                description: decl.title.clone(),
+                version: decl.version.clone().unwrap_or_default(),
+                members: vec![],
+                span: Some(decl.name_span),
+                file: None,
+                data_block: decl.block == BlockKind::DataBlock,
+                string: false,
+                section: section.map(|section| (decl.name.clone(), section)),
+            };
+            match build_udt(udt, &members, config, diagnostics) {
+                Ok(mut built) => udts.append(&mut built),
+                Err(err) => {
+                    failure.get_or_insert(err);
+                }
+            }
         }
     }
 
-    udts
+    if let Some(err) = failure {
+        return Err(err);
+    }
+
+    add_system_types(udts, config)
 }
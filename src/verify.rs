@@ -0,0 +1,214 @@
+use crate::error::Error;
+use crate::udt;
+use quick_xml::events::{BytesStart, Event};
+
+/// A member of a data type as written in the L5X
+struct Member {
+    name: String,
+    data_type: String,
+    hidden: bool,
+    target: Option<String>,
+    bit_number: Option<String>,
+}
+
+/// Get the unescaped value of an attribute of an element
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, Error> {
+    let read_error =
+        |err: quick_xml::Error| Error::Xml(format!("Failed to re-read the generated L5X: {}", err));
+
+    match element.try_get_attribute(name).map_err(read_error)? {
+        Some(value) => Ok(Some(
+            value.unescape_value().map_err(read_error)?.into_owned(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Re-read a generated L5X and check that every data type it uses is built in, defined by it
+/// or one of the known types, and that every BOOL packed into a hidden member names an
+/// existing hidden member and a bit from 0 to 7. Guards against regressions of the writer
+pub fn verify(xml: &[u8], known: &[String]) -> Result<(), Error> {
+    let mut reader = quick_xml::Reader::from_reader(xml);
+    let mut data_types: Vec<(String, Vec<Member>)> = vec![];
+    // Data types used outside of the members of a data type, with the element using them
+    let mut references: Vec<(String, String)> = vec![];
+
+    loop {
+        let element = match reader.read_event() {
+            Ok(Event::Start(element)) | Ok(Event::Empty(element)) => element,
+            Ok(Event::Eof) => break,
+            Ok(_) => continue,
+            Err(err) => {
+                return Err(Error::Xml(format!(
+                    "Failed to re-read the generated L5X at byte {}: {}",
+                    reader.buffer_position(),
+                    err
+                )))
+            }
+        };
+
+        let name = attribute(&element, "Name")?.unwrap_or_default();
+        match element.name().as_ref() {
+            b"DataType" => data_types.push((name, vec![])),
+            b"Member" => {
+                let Some((_, members)) = data_types.last_mut() else {
+                    continue;
+                };
+                members.push(Member {
+                    name,
+                    data_type: attribute(&element, "DataType")?.unwrap_or_default(),
+                    hidden: attribute(&element, "Hidden")?.as_deref() == Some("true"),
+                    target: attribute(&element, "Target")?,
+                    bit_number: attribute(&element, "BitNumber")?,
+                });
+            }
+            b"Parameter" | b"LocalTag" | b"Tag" => {
+                let data_type = attribute(&element, "DataType")?.unwrap_or_default();
+                references.push((name, data_type));
+            }
+            b"Dependency" => references.push((format!("Dependency {}", name), name)),
+            _ => {}
+        }
+    }
+
+    let resolves = |data_type: &str| {
+        udt::elementary_type(data_type)
+            || data_types
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(data_type))
+            || known
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(data_type))
+    };
+    let mut violations = vec![];
+
+    for (name, members) in &data_types {
+        for member in members {
+            let Some(target) = &member.target else {
+                if !resolves(&member.data_type) {
+                    violations.push(format!(
+                        "{}.{}: DataType {} is not defined",
+                        name, member.name, member.data_type
+                    ));
+                }
+                continue;
+            };
+
+            if member.data_type != "BIT" {
+                violations.push(format!(
+                    "{}.{}: has a Target but DataType {} instead of BIT",
+                    name, member.name, member.data_type
+                ));
+            }
+            if !members
+                .iter()
+                .any(|other| &other.name == target && other.hidden && other.target.is_none())
+            {
+                violations.push(format!(
+                    "{}.{}: Target {} is not a hidden member",
+                    name, member.name, target
+                ));
+            }
+            match member.bit_number.as_deref() {
+                Some(bit) if bit.parse::<u8>().is_ok_and(|bit| bit <= 7) => {}
+                Some(bit) => violations.push(format!(
+                    "{}.{}: BitNumber {} is not from 0 to 7",
+                    name, member.name, bit
+                )),
+                None => violations.push(format!("{}.{}: has no BitNumber", name, member.name)),
+            }
+        }
+    }
+
+    for (name, data_type) in &references {
+        if !resolves(data_type) {
+            violations.push(format!("{}: DataType {} is not defined", name, data_type));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Xml(format!(
+            "Generated L5X failed the self-check, this is a bug of the converter:\n{}",
+            violations.join("\n")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wrap members into an L5X with a single data type
+    fn document(members: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<RSLogix5000Content SchemaRevision="1.0" SoftwareRevision="35.00" TargetName="Motor" TargetType="DataType">
+<Controller Use="Context" Name="UdtConverter">
+<DataTypes Use="Context">
+<DataType Use="Target" Name="Motor" Family="NoFamily" Class="User">
+<Members>
+<Member Name="ZZZZZZZZZZMotor0" DataType="SINT" Dimension="0" Radix="Decimal" Hidden="true" ExternalAccess="Read/Write"/>
+{}
+</Members>
+</DataType>
+</DataTypes>
+</Controller>
+</RSLogix5000Content>"#,
+            members
+        )
+    }
+
+    /// Get the violations reported for the members
+    fn violations(members: &str) -> String {
+        match verify(document(members).as_bytes(), &[]) {
+            Err(Error::Xml(message)) => message,
+            Err(err) => panic!("expected an XML error, got {:?}", err),
+            Ok(()) => panic!("expected violations"),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_members() {
+        let members = r#"<Member Name="run" DataType="BIT" Dimension="0" Radix="Decimal" Hidden="false" Target="ZZZZZZZZZZMotor0" BitNumber="7" ExternalAccess="Read/Write"/>
+<Member Name="speed" DataType="REAL" Dimension="0" Radix="Float" Hidden="false" ExternalAccess="Read/Write"/>
+<Member Name="drive" DataType="Drive" Dimension="0" Radix="NullType" Hidden="false" ExternalAccess="Read/Write"/>"#;
+
+        assert!(verify(document(members).as_bytes(), &["DRIVE".to_string()]).is_ok());
+    }
+
+    #[test]
+    fn reports_missing_target() {
+        let message = violations(
+            r#"<Member Name="run" DataType="BIT" Dimension="0" Radix="Decimal" Hidden="false" Target="ZZZZZZZZZZMotor1" BitNumber="0" ExternalAccess="Read/Write"/>"#,
+        );
+        assert!(message.contains("Motor.run: Target ZZZZZZZZZZMotor1 is not a hidden member"));
+    }
+
+    #[test]
+    fn reports_target_that_is_not_hidden() {
+        let message = violations(
+            r#"<Member Name="flags" DataType="SINT" Dimension="0" Radix="Decimal" Hidden="false" ExternalAccess="Read/Write"/>
+<Member Name="run" DataType="BIT" Dimension="0" Radix="Decimal" Hidden="false" Target="flags" BitNumber="0" ExternalAccess="Read/Write"/>"#,
+        );
+        assert!(message.contains("Motor.run: Target flags is not a hidden member"));
+    }
+
+    #[test]
+    fn reports_bit_number_out_of_range() {
+        let message = violations(
+            r#"<Member Name="run" DataType="BIT" Dimension="0" Radix="Decimal" Hidden="false" Target="ZZZZZZZZZZMotor0" BitNumber="8" ExternalAccess="Read/Write"/>"#,
+        );
+        assert!(message.contains("Motor.run: BitNumber 8 is not from 0 to 7"));
+    }
+
+    #[test]
+    fn reports_undefined_data_type() {
+        let message = violations(
+            r#"<Member Name="drive" DataType="Drive" Dimension="0" Radix="NullType" Hidden="false" ExternalAccess="Read/Write"/>"#,
+        );
+        assert!(message.contains("Motor.drive: DataType Drive is not defined"));
+        assert!(message.starts_with("Generated L5X failed the self-check"));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::cli::ConvertArgs;
+use crate::config::Config;
+use crate::error::Error;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Time to wait for further file events before converting, since an export
+/// from TIA Portal usually triggers several events for the same file
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Check if a changed file should trigger a new conversion
+fn is_relevant(path: &Path, input: &Path) -> bool {
+    crate::is_source_file(path)
+        || (path.file_name().is_some() && path.file_name() == input.file_name())
+}
+
+/// Run the conversion once, reporting but not returning errors.
+/// Returns whether the conversion succeeded
+fn run(args: &ConvertArgs, config: &Config) -> bool {
+    match crate::convert(args, config) {
+        Ok(_) => {
+            log::info!("Converted {}", args.input().display());
+            true
+        }
+        Err(err) => {
+            crate::diagnostic::print_error(None, &err);
+            false
+        }
+    }
+}
+
+/// Convert the input, then convert it again every time it changes
+pub fn watch(args: &ConvertArgs, config: &Config) -> Result<(), Error> {
+    let input = args.input();
+
+    if input == Path::new(crate::STDIO_PATH) {
+        return Err(Error::Usage("Cannot watch stdin".to_string()));
+    }
+
+    // Watch the parent of single files, because exports often replace the file
+    // instead of modifying it, which would end a watch on the file itself
+    let watch_path = if input.is_dir() {
+        input.to_path_buf()
+    } else if crate::is_glob(input) {
+        crate::glob_base(input)
+    } else {
+        input
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf()
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)
+        .map_err(|err| Error::Io(format!("Failed to start watching: {}", err)))?;
+    watcher
+        .watch(&watch_path, RecursiveMode::Recursive)
+        .map_err(|err| Error::Io(format!("Failed to watch {}: {}", watch_path.display(), err)))?;
+
+    // Once this watch has written the output, later conversions may replace it
+    let forced = ConvertArgs {
+        force: true,
+        ..args.clone()
+    };
+    let mut written = run(args, config);
+
+    log::info!("Watching {} for changes...", watch_path.display());
+
+    while let Ok(event) = rx.recv() {
+        let event = event.map_err(|err| Error::Io(format!("Failed to watch: {}", err)))?;
+
+        if matches!(event.kind, EventKind::Access(_))
+            || !event.paths.iter().any(|path| is_relevant(path, input))
+        {
+            continue;
+        }
+
+        log::debug!("Change detected in {:?}", event.paths);
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+        written |= run(if written { &forced } else { args }, config);
+    }
+
+    Ok(())
+}